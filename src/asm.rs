@@ -0,0 +1,494 @@
+//! An assembler for the Cowgod syntax produced by `disasm`.
+//!
+//! Each line holds an optional `label:`, then an instruction, a `DB`/`:byte`
+//! data list or a `:const NAME VALUE` definition. Comments start with `;`.
+//! Numbers are decimal, `#1F`/`0x1F` hex or `0b1010` binary, and anywhere a
+//! number is expected a label or constant may be used instead. XO-CHIP's
+//! F000 NNNN is written `LD I, LONG NNNN`.
+
+use crate::chip8::Chip8;
+use crate::error::AsmError;
+use std::collections::HashMap;
+
+/// Assembles `source` into a ROM image to be loaded at 0x200.
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    let mut symbols = HashMap::new();
+    let mut items = Vec::new();
+    let mut addr = Chip8::PC_START;
+
+    for (n, text) in source.lines().enumerate() {
+        let line = Line::new(n + 1, text);
+        let (label, head, operands) = line.tokens();
+
+        if let Some(label) = label {
+            line.check_name(label.text, label.column)?;
+            // A program may fill memory, but nothing can be addressed past it.
+            if addr > 0xFFFF {
+                return Err(line.error(label.column, "label is past the end of memory"));
+            }
+            define(&mut symbols, label.text, addr as u16, &line, label.column)?;
+        }
+        let head = match head {
+            Some(head) => head,
+            None => continue,
+        };
+        if operands.iter().any(|op| op.text.is_empty()) {
+            return Err(line.error(head.column, "empty operand"));
+        }
+
+        let item = match head.text.to_ascii_uppercase().as_str() {
+            ":CONST" => {
+                let (name, value) = match operands.as_slice() {
+                    [name, value] => (name, value),
+                    _ => return Err(line.error(head.column, ":const needs a name and a value")),
+                };
+                line.check_name(name.text, name.column)?;
+                let value = line.value(value, &symbols, 0xFFFF)?;
+                define(&mut symbols, name.text, value, &line, name.column)?;
+                continue;
+            }
+            ":BYTE" | "DB" => {
+                if operands.is_empty() {
+                    return Err(line.error(head.column, "expected at least one byte"));
+                }
+                Item::Bytes(operands)
+            }
+            mnemonic if mnemonic.starts_with(':') => {
+                return Err(line.error(head.column, format!("unknown directive {}", head.text)));
+            }
+            _ => Item::Instruction(head, operands),
+        };
+        let size = match &item {
+            Item::Bytes(bytes) => bytes.len(),
+            Item::Instruction(_, operands) => {
+                let long = operands
+                    .iter()
+                    .any(|op| op.text.to_ascii_uppercase().starts_with("LONG "));
+                if long {
+                    4
+                } else {
+                    2
+                }
+            }
+        };
+        items.push((line, item));
+        addr += size;
+        if addr > 0x10000 {
+            let (line, _) = items.last().unwrap();
+            return Err(line.error(1, "program does not fit in memory"));
+        }
+    }
+
+    let mut rom = Vec::with_capacity(addr - Chip8::PC_START);
+    for (line, item) in items.iter() {
+        match item {
+            Item::Bytes(bytes) => {
+                for byte in bytes {
+                    rom.push(line.value(byte, &symbols, 0xFF)? as u8);
+                }
+            }
+            Item::Instruction(mnemonic, tokens) => {
+                let operands = tokens
+                    .iter()
+                    .map(|op| line.operand(op, &symbols))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let words = encode(&mnemonic.text.to_ascii_uppercase(), &operands).map_err(
+                    |err| match err {
+                        EncodeError::Operands => line.error(mnemonic.column, "invalid operands"),
+                        EncodeError::TooBig { operand, max } => {
+                            let token = tokens[operand];
+                            let message = format!("{} does not fit in {:#X}", token.text, max);
+                            line.error(token.column, message)
+                        }
+                    },
+                )?;
+                for word in words {
+                    rom.extend_from_slice(&word.to_be_bytes());
+                }
+            }
+        }
+    }
+    Ok(rom)
+}
+
+fn define(
+    symbols: &mut HashMap<String, u16>,
+    name: &str,
+    value: u16,
+    line: &Line,
+    column: usize,
+) -> Result<(), AsmError> {
+    if symbols.insert(name.to_string(), value).is_some() {
+        return Err(line.error(column, format!("{} is already defined", name)));
+    }
+    Ok(())
+}
+
+enum Item<'a> {
+    Bytes(Vec<Token<'a>>),
+    Instruction(Token<'a>, Vec<Token<'a>>),
+}
+
+#[derive(Clone, Copy)]
+struct Token<'a> {
+    text: &'a str,
+    column: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operand {
+    V(u16),
+    VRange(u16, u16),
+    I,
+    IndirectI,
+    Long(u16),
+    Value(u16),
+    Dt,
+    St,
+    K,
+    F,
+    Hf,
+    B,
+    R,
+}
+
+struct Line<'a> {
+    number: usize,
+    text: &'a str,
+}
+
+impl<'a> Line<'a> {
+    fn new(number: usize, text: &'a str) -> Self {
+        let text = match text.find(';') {
+            Some(comment) => &text[..comment],
+            None => text,
+        };
+        Line { number, text }
+    }
+
+    fn error<S: Into<String>>(&self, column: usize, message: S) -> AsmError {
+        AsmError {
+            line: self.number,
+            column,
+            message: message.into(),
+        }
+    }
+
+    // Splits the line into an optional label, the mnemonic or directive, and
+    // its operands. Operands are separated by commas, except for directives
+    // and data lists where spaces work too.
+    fn tokens(&self) -> (Option<Token<'a>>, Option<Token<'a>>, Vec<Token<'a>>) {
+        let mut pos = 0;
+        let mut head = self.word(&mut pos);
+        let mut label = None;
+        if let Some(word) = head {
+            if word.text.ends_with(':') && !word.text.starts_with(':') {
+                label = Some(Token {
+                    text: &word.text[..word.text.len() - 1],
+                    column: word.column,
+                });
+                head = self.word(&mut pos);
+            }
+        }
+
+        let lists = head
+            .is_some_and(|head| head.text.starts_with(':') || head.text.eq_ignore_ascii_case("DB"));
+        let separators: &[char] = if lists { &[',', ' ', '\t'] } else { &[','] };
+        let mut operands = Vec::new();
+        for piece in self.text[pos..].split(separators) {
+            let offset = piece.as_ptr() as usize - self.text.as_ptr() as usize;
+            let text = piece.trim();
+            if !text.is_empty() || !lists {
+                let column = offset + piece.len() - piece.trim_start().len() + 1;
+                operands.push(Token { text, column });
+            }
+        }
+        if operands.len() == 1 && operands[0].text.is_empty() {
+            operands.clear();
+        }
+        (label, head, operands)
+    }
+
+    // The next whitespace-delimited word at or after `pos`.
+    fn word(&self, pos: &mut usize) -> Option<Token<'a>> {
+        let rest = &self.text[*pos..];
+        let start = *pos + rest.len() - rest.trim_start().len();
+        let len = self.text[start..]
+            .find(char::is_whitespace)
+            .unwrap_or(self.text.len() - start);
+        *pos = start + len;
+        if len == 0 {
+            return None;
+        }
+        Some(Token {
+            text: &self.text[start..start + len],
+            column: start + 1,
+        })
+    }
+
+    fn check_name(&self, name: &str, column: usize) -> Result<(), AsmError> {
+        let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if !valid || Line::keyword(name).is_some() {
+            return Err(self.error(column, format!("{} is not a valid name", name)));
+        }
+        Ok(())
+    }
+
+    fn register(text: &str) -> Option<u16> {
+        let digit = text.strip_prefix('V').or_else(|| text.strip_prefix('v'))?;
+        if digit.len() != 1 {
+            return None;
+        }
+        u16::from_str_radix(digit, 16).ok()
+    }
+
+    fn value(
+        &self,
+        token: &Token,
+        symbols: &HashMap<String, u16>,
+        max: u16,
+    ) -> Result<u16, AsmError> {
+        let text = token.text;
+        let parsed = if let Some(hex) = text.strip_prefix('#') {
+            u32::from_str_radix(hex, 16).ok()
+        } else if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+            u32::from_str_radix(hex, 16).ok()
+        } else if let Some(bin) = text.strip_prefix("0b").or_else(|| text.strip_prefix("0B")) {
+            u32::from_str_radix(bin, 2).ok()
+        } else if text.starts_with(|c: char| c.is_ascii_digit()) {
+            text.parse().ok()
+        } else {
+            let value = symbols
+                .get(text)
+                .ok_or_else(|| self.error(token.column, format!("undefined symbol {}", text)))?;
+            Some(u32::from(*value))
+        };
+        match parsed {
+            Some(value) if value <= u32::from(max) => Ok(value as u16),
+            Some(_) => {
+                Err(self.error(token.column, format!("{} does not fit in {:#X}", text, max)))
+            }
+            None => Err(self.error(token.column, format!("bad number {}", text))),
+        }
+    }
+
+    // Operands spelled with reserved words or registers, which can't be used
+    // as names.
+    fn keyword(text: &str) -> Option<Operand> {
+        let upper = text.to_ascii_uppercase();
+        let operand = match upper.as_str() {
+            "I" => Operand::I,
+            "[I]" => Operand::IndirectI,
+            "DT" => Operand::Dt,
+            "ST" => Operand::St,
+            "K" => Operand::K,
+            "F" => Operand::F,
+            "HF" => Operand::Hf,
+            "B" => Operand::B,
+            "R" => Operand::R,
+            _ => match upper.split_once('-') {
+                Some((x, y)) => {
+                    Operand::VRange(Line::register(x.trim())?, Line::register(y.trim())?)
+                }
+                None => Operand::V(Line::register(&upper)?),
+            },
+        };
+        Some(operand)
+    }
+
+    fn operand(&self, token: &Token, symbols: &HashMap<String, u16>) -> Result<Operand, AsmError> {
+        if let Some(operand) = Line::keyword(token.text) {
+            return Ok(operand);
+        }
+        let upper = token.text.to_ascii_uppercase();
+        if let Some(long) = upper.strip_prefix("LONG ") {
+            let start = token.text.len() - long.len();
+            let value = Token {
+                text: token.text[start..].trim(),
+                column: token.column + start,
+            };
+            return Ok(Operand::Long(self.value(&value, symbols, 0xFFFF)?));
+        }
+        Ok(Operand::Value(self.value(token, symbols, 0xFFFF)?))
+    }
+}
+
+enum EncodeError {
+    // No form of the mnemonic takes these operands.
+    Operands,
+    // The operand at this index is larger than `max`.
+    TooBig { operand: usize, max: u16 },
+}
+
+// Checks that the value of operand `operand` is at most `max`.
+fn fit(operand: usize, value: u16, max: u16) -> Result<u16, EncodeError> {
+    if value <= max {
+        Ok(value)
+    } else {
+        Err(EncodeError::TooBig { operand, max })
+    }
+}
+
+// Encodes an instruction as one word, or two for F000 NNNN.
+fn encode(mnemonic: &str, operands: &[Operand]) -> Result<Vec<u16>, EncodeError> {
+    use self::Operand::*;
+
+    let word = match (mnemonic, operands) {
+        ("CLS", []) => 0x00E0,
+        ("RET", []) => 0x00EE,
+        ("SCD", [Value(n)]) => 0x00C0 | fit(0, *n, 0xF)?,
+        ("SCU", [Value(n)]) => 0x00D0 | fit(0, *n, 0xF)?,
+        ("SCR", []) => 0x00FB,
+        ("SCL", []) => 0x00FC,
+        ("EXIT", []) => 0x00FD,
+        ("LOW", []) => 0x00FE,
+        ("HIGH", []) => 0x00FF,
+        ("SYS", [Value(nnn)]) => fit(0, *nnn, 0xFFF)?,
+        ("JP", [Value(nnn)]) => 0x1000 | fit(0, *nnn, 0xFFF)?,
+        ("JP", [V(0), Value(nnn)]) => 0xB000 | fit(1, *nnn, 0xFFF)?,
+        ("CALL", [Value(nnn)]) => 0x2000 | fit(0, *nnn, 0xFFF)?,
+        ("SE", [V(x), Value(nn)]) => 0x3000 | x << 8 | fit(1, *nn, 0xFF)?,
+        ("SNE", [V(x), Value(nn)]) => 0x4000 | x << 8 | fit(1, *nn, 0xFF)?,
+        ("SE", [V(x), V(y)]) => 0x5000 | x << 8 | y << 4,
+        ("LD", [IndirectI, VRange(x, y)]) => 0x5002 | x << 8 | y << 4,
+        ("LD", [VRange(x, y), IndirectI]) => 0x5003 | x << 8 | y << 4,
+        ("LD", [V(x), Value(nn)]) => 0x6000 | x << 8 | fit(1, *nn, 0xFF)?,
+        ("ADD", [V(x), Value(nn)]) => 0x7000 | x << 8 | fit(1, *nn, 0xFF)?,
+        ("LD", [V(x), V(y)]) => 0x8000 | x << 8 | y << 4,
+        ("OR", [V(x), V(y)]) => 0x8001 | x << 8 | y << 4,
+        ("AND", [V(x), V(y)]) => 0x8002 | x << 8 | y << 4,
+        ("XOR", [V(x), V(y)]) => 0x8003 | x << 8 | y << 4,
+        ("ADD", [V(x), V(y)]) => 0x8004 | x << 8 | y << 4,
+        ("SUB", [V(x), V(y)]) => 0x8005 | x << 8 | y << 4,
+        ("SHR", [V(x)]) => 0x8006 | x << 8 | x << 4,
+        ("SHR", [V(x), V(y)]) => 0x8006 | x << 8 | y << 4,
+        ("SUBN", [V(x), V(y)]) => 0x8007 | x << 8 | y << 4,
+        ("SHL", [V(x)]) => 0x800E | x << 8 | x << 4,
+        ("SHL", [V(x), V(y)]) => 0x800E | x << 8 | y << 4,
+        ("SNE", [V(x), V(y)]) => 0x9000 | x << 8 | y << 4,
+        ("LD", [I, Value(nnn)]) => 0xA000 | fit(1, *nnn, 0xFFF)?,
+        ("RND", [V(x), Value(nn)]) => 0xC000 | x << 8 | fit(1, *nn, 0xFF)?,
+        ("DRW", [V(x), V(y), Value(n)]) => 0xD000 | x << 8 | y << 4 | fit(2, *n, 0xF)?,
+        ("SKP", [V(x)]) => 0xE09E | x << 8,
+        ("SKNP", [V(x)]) => 0xE0A1 | x << 8,
+        ("LD", [I, Long(nnnn)]) => return Ok(vec![0xF000, *nnnn]),
+        ("PLANE", [Value(n)]) => 0xF001 | fit(0, *n, 0x3)? << 8,
+        ("AUDIO", []) => 0xF002,
+        ("LD", [V(x), Dt]) => 0xF007 | x << 8,
+        ("LD", [V(x), K]) => 0xF00A | x << 8,
+        ("LD", [Dt, V(x)]) => 0xF015 | x << 8,
+        ("LD", [St, V(x)]) => 0xF018 | x << 8,
+        ("ADD", [I, V(x)]) => 0xF01E | x << 8,
+        ("LD", [F, V(x)]) => 0xF029 | x << 8,
+        ("LD", [Hf, V(x)]) => 0xF030 | x << 8,
+        ("LD", [B, V(x)]) => 0xF033 | x << 8,
+        ("PITCH", [V(x)]) => 0xF03A | x << 8,
+        ("LD", [IndirectI, V(x)]) => 0xF055 | x << 8,
+        ("LD", [V(x), IndirectI]) => 0xF065 | x << 8,
+        ("LD", [R, V(x)]) => 0xF075 | x << 8,
+        ("LD", [V(x), R]) => 0xF085 | x << 8,
+        _ => return Err(EncodeError::Operands),
+    };
+    Ok(vec![word])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(source: &str) -> (usize, usize, String) {
+        let err = assemble(source).unwrap_err();
+        (err.line, err.column, err.message)
+    }
+
+    #[test]
+    fn labels_and_constants() {
+        let source = "\
+            :const SPEED 3\n\
+            start: LD V0, SPEED ; comment\n\
+            \tJP end\n\
+            end:\n\
+            \tJP start\n";
+        assert_eq!(
+            assemble(source),
+            Ok(vec![0x60, 0x03, 0x12, 0x04, 0x12, 0x00])
+        );
+    }
+
+    #[test]
+    fn long_and_data() {
+        let source = "LD I, LONG data\ndata: DB #FF, 0b101, 7\n:byte 0x10 0x20\n";
+        assert_eq!(
+            assemble(source),
+            Ok(vec![0xF0, 0x00, 0x02, 0x04, 0xFF, 0x05, 0x07, 0x10, 0x20])
+        );
+    }
+
+    #[test]
+    fn errors_point_at_the_problem() {
+        assert_eq!(
+            error("CLS\nLD V0, 300\n"),
+            (2, 8, "300 does not fit in 0xFF".to_string())
+        );
+        assert_eq!(
+            error("DRW V0, V1, 16"),
+            (1, 13, "16 does not fit in 0xF".to_string())
+        );
+        assert_eq!(
+            error("  JP V1, 0x300"),
+            (1, 3, "invalid operands".to_string())
+        );
+        assert_eq!(
+            error("JP nowhere"),
+            (1, 4, "undefined symbol nowhere".to_string())
+        );
+        assert_eq!(
+            error("a: CLS\na: CLS"),
+            (2, 1, "a is already defined".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_reserved_names() {
+        for name in ["I", "dt", "ST", "K", "F", "HF", "b", "R", "VA", "v1-v2"] {
+            let source = format!("{}: CLS\nJP {}\n", name, name);
+            assert_eq!(
+                error(&source),
+                (1, 1, format!("{} is not a valid name", name))
+            );
+        }
+        assert_eq!(
+            error(":const HF 1"),
+            (1, 8, "HF is not a valid name".to_string())
+        );
+        assert!(assemble("v1-loop: JP v1-loop").is_ok());
+    }
+
+    #[test]
+    fn rejects_programs_past_the_end_of_memory() {
+        // 0xFE00 bytes fill everything from 0x200 up.
+        let fill = format!("DB {}\n", vec!["0"; 0x100].join(", ")).repeat(0xFE);
+        assert_eq!(assemble(&fill).map(|rom| rom.len()), Ok(0xFE00));
+        assert_eq!(
+            error(&format!("{}end:\n", fill)),
+            (255, 1, "label is past the end of memory".to_string())
+        );
+        assert_eq!(
+            error(&format!("{}CLS\n", fill)),
+            (255, 1, "program does not fit in memory".to_string())
+        );
+        // The four byte F000 NNNN can't start two bytes from the end.
+        let almost = format!(
+            "{}DB {}\n",
+            &fill[..fill.len() / 0xFE * 0xFD],
+            vec!["0"; 0xFE].join(", ")
+        );
+        assert_eq!(
+            error(&format!("{}LD I, LONG 0\n", almost)),
+            (255, 1, "program does not fit in memory".to_string())
+        );
+    }
+}
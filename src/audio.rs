@@ -0,0 +1,332 @@
+//! Sound output for the sound timer.
+
+use crate::chip8::Chip8;
+use crate::scheduler::Scheduler;
+use std::io::{self, Seek, SeekFrom, Write};
+
+/// What the machine plays during a frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tone<'a> {
+    Off,
+    /// The plain CHIP-8 beep.
+    Beep,
+    /// An XO-CHIP pattern of 128 one-bit samples, most significant bit
+    /// first, looped at `rate` samples per second.
+    Pattern {
+        bits: &'a [u8],
+        rate: f64,
+    },
+}
+
+impl<'a> Tone<'a> {
+    /// The tone `chip8` plays while its sound timer runs. XO-CHIP machines
+    /// play their audio pattern, or the beep until F002 has loaded one.
+    pub fn of(chip8: &'a Chip8) -> Self {
+        let bits = chip8.audio_pattern();
+        if chip8.sound_timer() == 0 {
+            Tone::Off
+        } else if chip8.is_xo_chip() && bits.iter().any(|&b| b != 0) {
+            Tone::Pattern {
+                bits,
+                rate: chip8.audio_rate(),
+            }
+        } else {
+            Tone::Beep
+        }
+    }
+}
+
+/// Something that can play the CHIP-8 tone. Front ends call `frame` once per
+/// 60 Hz frame with the tone to play, usually `Tone::of` the machine.
+pub trait AudioSink {
+    fn frame(&mut self, tone: Tone) -> io::Result<()>;
+
+    /// Called once when output ends, for sinks that need to finish a file.
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Rings the terminal bell each time the tone starts.
+pub struct Bell<W: Write> {
+    out: W,
+    on: bool,
+}
+
+impl<W: Write> Bell<W> {
+    pub fn new(out: W) -> Self {
+        Bell { out, on: false }
+    }
+}
+
+impl<W: Write> AudioSink for Bell<W> {
+    fn frame(&mut self, tone: Tone) -> io::Result<()> {
+        let on = tone != Tone::Off;
+        if on && !self.on {
+            self.out.write_all(b"\x07")?;
+            self.out.flush()?;
+        }
+        self.on = on;
+        Ok(())
+    }
+}
+
+/// Generates a square wave as signed 16-bit mono samples, a frame at a time.
+/// XO-CHIP patterns are played at the same volume instead of the wave.
+pub struct SquareWave {
+    frequency: f64,
+    volume: f64,
+    sample_rate: u32,
+    phase: f64,
+    // Position in the XO-CHIP pattern, in bits.
+    pattern_phase: f64,
+    remainder: u32,
+}
+
+impl SquareWave {
+    pub const DEFAULT_FREQUENCY: f64 = 440.0;
+    pub const DEFAULT_VOLUME: f64 = 0.25;
+    pub const DEFAULT_SAMPLE_RATE: u32 = 44100;
+
+    /// A wave of `frequency` Hz at `volume` between 0 and 1 of full scale.
+    pub fn new(frequency: f64, volume: f64, sample_rate: u32) -> Self {
+        SquareWave {
+            frequency,
+            volume: volume.clamp(0.0, 1.0),
+            sample_rate,
+            phase: 0.0,
+            pattern_phase: 0.0,
+            remainder: 0,
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Appends one frame's worth of `tone` to `out`. Frames alternate in
+    /// length when the sample rate isn't a multiple of the frame rate, so
+    /// that none are lost over time.
+    pub fn frame(&mut self, tone: Tone, out: &mut Vec<i16>) {
+        self.remainder += self.sample_rate;
+        let count = self.remainder / Scheduler::FRAME_RATE;
+        self.remainder %= Scheduler::FRAME_RATE;
+
+        let amplitude = (self.volume * f64::from(i16::MAX)) as i16;
+        let sample_rate = f64::from(self.sample_rate);
+        let step = self.frequency / sample_rate;
+        for _ in 0..count {
+            let high = match tone {
+                Tone::Off => {
+                    out.push(0);
+                    continue;
+                }
+                Tone::Beep => self.phase < 0.5,
+                Tone::Pattern { bits, rate } => {
+                    let bit = self.pattern_phase as usize;
+                    self.pattern_phase =
+                        (self.pattern_phase + rate / sample_rate) % (bits.len() * 8) as f64;
+                    bits[bit / 8] & (0x80 >> (bit % 8)) != 0
+                }
+            };
+            out.push(if high { amplitude } else { -amplitude });
+            self.phase = (self.phase + step).fract();
+        }
+    }
+}
+
+impl Default for SquareWave {
+    fn default() -> Self {
+        SquareWave::new(
+            SquareWave::DEFAULT_FREQUENCY,
+            SquareWave::DEFAULT_VOLUME,
+            SquareWave::DEFAULT_SAMPLE_RATE,
+        )
+    }
+}
+
+fn write_samples<W: Write>(out: &mut W, samples: &[i16]) -> io::Result<()> {
+    let bytes: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+    out.write_all(&bytes)
+}
+
+/// Streams a square wave as raw signed 16-bit little-endian mono samples,
+/// e.g. into a pipe to `aplay -f S16_LE -r 44100`.
+pub struct Pcm<W: Write> {
+    out: W,
+    wave: SquareWave,
+    samples: Vec<i16>,
+}
+
+impl<W: Write> Pcm<W> {
+    pub fn new(out: W, wave: SquareWave) -> Self {
+        Pcm {
+            out,
+            wave,
+            samples: Vec::new(),
+        }
+    }
+}
+
+impl<W: Write> AudioSink for Pcm<W> {
+    fn frame(&mut self, tone: Tone) -> io::Result<()> {
+        self.samples.clear();
+        self.wave.frame(tone, &mut self.samples);
+        write_samples(&mut self.out, &self.samples)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Writes a square wave to a mono 16-bit PCM WAV file. The sizes in the
+/// header are filled in by `finish`.
+pub struct Wav<W: Write + Seek> {
+    out: W,
+    wave: SquareWave,
+    samples: Vec<i16>,
+    data_len: u32,
+}
+
+impl<W: Write + Seek> Wav<W> {
+    const HEADER_LEN: u32 = 44;
+
+    pub fn new(mut out: W, wave: SquareWave) -> io::Result<Self> {
+        let rate = wave.sample_rate();
+        let mut header = Vec::with_capacity(Wav::<W>::HEADER_LEN as usize);
+        header.extend_from_slice(b"RIFF");
+        header.extend_from_slice(&(Wav::<W>::HEADER_LEN - 8).to_le_bytes());
+        header.extend_from_slice(b"WAVEfmt ");
+        header.extend_from_slice(&16u32.to_le_bytes());
+        // PCM, one channel.
+        header.extend_from_slice(&1u16.to_le_bytes());
+        header.extend_from_slice(&1u16.to_le_bytes());
+        header.extend_from_slice(&rate.to_le_bytes());
+        header.extend_from_slice(&(rate * 2).to_le_bytes());
+        // Bytes per sample and bits per sample.
+        header.extend_from_slice(&2u16.to_le_bytes());
+        header.extend_from_slice(&16u16.to_le_bytes());
+        header.extend_from_slice(b"data");
+        header.extend_from_slice(&0u32.to_le_bytes());
+        out.write_all(&header)?;
+        Ok(Wav {
+            out,
+            wave,
+            samples: Vec::new(),
+            data_len: 0,
+        })
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+impl<W: Write + Seek> AudioSink for Wav<W> {
+    fn frame(&mut self, tone: Tone) -> io::Result<()> {
+        self.samples.clear();
+        self.wave.frame(tone, &mut self.samples);
+        write_samples(&mut self.out, &self.samples)?;
+        self.data_len += self.samples.len() as u32 * 2;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.out.seek(SeekFrom::Start(4))?;
+        self.out
+            .write_all(&(Wav::<W>::HEADER_LEN - 8 + self.data_len).to_le_bytes())?;
+        self.out.seek(SeekFrom::Start(40))?;
+        self.out.write_all(&self.data_len.to_le_bytes())?;
+        self.out.seek(SeekFrom::End(0))?;
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn bell_rings_when_tone_starts() {
+        let mut bell = Bell::new(Vec::new());
+        for &tone in &[Tone::Off, Tone::Beep, Tone::Beep, Tone::Off, Tone::Beep] {
+            bell.frame(tone).unwrap();
+        }
+        assert_eq!(bell.out, b"\x07\x07");
+    }
+
+    #[test]
+    fn square_wave_frames() {
+        let mut wave = SquareWave::new(100.0, 0.5, 1000);
+        let mut samples = Vec::new();
+        wave.frame(Tone::Off, &mut samples);
+        assert_eq!(samples.len(), 16);
+        assert!(samples.iter().all(|&s| s == 0));
+
+        // 1000 samples a second don't divide into 60 frames evenly.
+        for _ in 1..Scheduler::FRAME_RATE {
+            wave.frame(Tone::Beep, &mut samples);
+        }
+        assert_eq!(samples.len(), 1000);
+        let amplitude = i16::MAX / 2;
+        assert!(samples[16..].iter().all(|&s| s.abs() == amplitude));
+        assert_ne!(samples[16], samples[21]);
+    }
+
+    #[test]
+    fn plays_xo_chip_patterns() {
+        let mut chip8 = Chip8::new_xo_chip();
+        // LD I, 20A; AUDIO; LD V0, 30; LD ST, V0; then the pattern.
+        let mut rom = vec![0xA2, 0x0A, 0xF0, 0x02, 0x60, 0x1E, 0xF0, 0x18, 0x00, 0x00];
+        rom.extend_from_slice(&[0xF0; 16]);
+        chip8.load_rom(&rom).unwrap();
+        assert_eq!(Tone::of(&chip8), Tone::Off);
+        chip8.run_frame(4).unwrap();
+
+        // Two samples per bit at the default pitch's 4000 Hz.
+        let tone = Tone::of(&chip8);
+        assert_eq!(
+            tone,
+            Tone::Pattern {
+                bits: &[0xF0; 16],
+                rate: 4000.0
+            }
+        );
+        let mut wave = SquareWave::new(440.0, 0.5, 8000);
+        let mut samples = Vec::new();
+        wave.frame(tone, &mut samples);
+        let amplitude = i16::MAX / 2;
+        assert!(samples[..8].iter().all(|&s| s == amplitude));
+        assert!(samples[8..16].iter().all(|&s| s == -amplitude));
+        assert_eq!(samples[128], amplitude);
+    }
+
+    #[test]
+    fn beeps_without_a_pattern() {
+        let mut chip8 = Chip8::new();
+        // LD V0, 30; LD ST, V0
+        chip8.load_rom(&[0x60, 0x1E, 0xF0, 0x18]).unwrap();
+        chip8.run_frame(2).unwrap();
+        assert_eq!(Tone::of(&chip8), Tone::Beep);
+    }
+
+    #[test]
+    fn wav_header_sizes() {
+        let mut wav = Wav::new(Cursor::new(Vec::new()), SquareWave::default()).unwrap();
+        wav.frame(Tone::Beep).unwrap();
+        wav.frame(Tone::Off).unwrap();
+        wav.finish().unwrap();
+        let data = wav.into_inner().into_inner();
+
+        let data_len = 2 * 735 * 2;
+        assert_eq!(data.len(), 44 + data_len);
+        assert_eq!(&data[0..4], b"RIFF");
+        assert_eq!(&data[4..8], &(36 + data_len as u32).to_le_bytes());
+        assert_eq!(&data[8..16], b"WAVEfmt ");
+        assert_eq!(&data[24..28], &44100u32.to_le_bytes());
+        assert_eq!(&data[36..40], b"data");
+        assert_eq!(&data[40..44], &(data_len as u32).to_le_bytes());
+    }
+}
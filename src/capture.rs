@@ -0,0 +1,306 @@
+//! Recording the screen to animations and video streams.
+//!
+//! Every frame is captured at the size of the SUPER-CHIP hi-res screen times
+//! a scale, with lo-res pixels drawn twice as big, so that a program
+//! switching resolution doesn't change the size of the recording.
+
+use crate::chip8::Chip8;
+use crate::scheduler::Scheduler;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// RGB colour for each combination of lit bitplanes: none, the first plane
+/// only, the second plane only, and both.
+pub type Palette = [[u8; 3]; 4];
+
+/// Black and white, with greys for the second plane.
+pub const DEFAULT_PALETTE: Palette = [[0x00; 3], [0xFF; 3], [0xAA; 3], [0x55; 3]];
+
+/// Something that can record the screen. Front ends call `frame` once per
+/// 60 Hz frame.
+pub trait FrameSink {
+    fn frame(&mut self, chip8: &Chip8) -> io::Result<()>;
+
+    /// Called once when recording ends, for sinks that need to finish a file.
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Width and height in pixels of every frame captured at `scale`.
+pub fn frame_size(scale: u16) -> (usize, usize) {
+    let scale = usize::from(scale.max(1));
+    (Chip8::HIRES_WIDTH * scale, Chip8::HIRES_HEIGHT * scale)
+}
+
+// Replaces `out` with the palette index of every pixel of a frame.
+fn capture(chip8: &Chip8, scale: u16, out: &mut Vec<u8>) {
+    let (width, height) = frame_size(scale);
+    let size = Chip8::HIRES_WIDTH / chip8.width() * usize::from(scale.max(1));
+    let pixels = chip8.framebuffer();
+    out.clear();
+    for y in 0..height {
+        let row = &pixels[y / size * chip8.width()..][..chip8.width()];
+        out.extend((0..width).map(|x| row[x / size] & 0x3));
+    }
+}
+
+/// Encodes an animated GIF that loops forever. Runs of identical frames
+/// become one longer frame, and a frame shown for less than the 1/50 of a
+/// second most viewers can manage is merged into the next.
+pub struct Gif<W: Write> {
+    encoder: Option<gif::Encoder<W>>,
+    scale: u16,
+    // The frame waiting to be written and the frame number it was first
+    // seen at, out of all the frames captured so far.
+    pending: Vec<u8>,
+    start: u64,
+    frames: u64,
+    pixels: Vec<u8>,
+}
+
+impl<W: Write> Gif<W> {
+    // GIF delays are in hundredths of a second.
+    const MIN_DELAY: u64 = 2;
+
+    pub fn new(out: W, scale: u16, palette: Palette) -> io::Result<Self> {
+        let (width, height) = frame_size(scale);
+        let mut encoder = gif::Encoder::new(out, width as u16, height as u16, &palette.concat())
+            .map_err(gif_error)?;
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .map_err(gif_error)?;
+        Ok(Gif {
+            encoder: Some(encoder),
+            scale,
+            pending: Vec::new(),
+            start: 0,
+            frames: 0,
+            pixels: Vec::new(),
+        })
+    }
+
+    // Hundredths of a second from the start to frame `frame`.
+    fn time(frame: u64) -> u64 {
+        frame * 100 / u64::from(Scheduler::FRAME_RATE)
+    }
+
+    fn write_pending(&mut self) -> io::Result<()> {
+        let delay = Gif::<W>::time(self.frames) - Gif::<W>::time(self.start);
+        let (width, height) = frame_size(self.scale);
+        let mut frame =
+            gif::Frame::from_indexed_pixels(width as u16, height as u16, &self.pending[..], None);
+        frame.delay = delay.min(u64::from(u16::MAX)) as u16;
+        match &mut self.encoder {
+            Some(encoder) => encoder.write_frame(&frame).map_err(gif_error),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<W: Write> FrameSink for Gif<W> {
+    fn frame(&mut self, chip8: &Chip8) -> io::Result<()> {
+        capture(chip8, self.scale, &mut self.pixels);
+        if self.frames == 0 {
+            std::mem::swap(&mut self.pending, &mut self.pixels);
+        } else if self.pixels != self.pending {
+            if Gif::<W>::time(self.frames) - Gif::<W>::time(self.start) >= Gif::<W>::MIN_DELAY {
+                self.write_pending()?;
+                self.start = self.frames;
+            }
+            std::mem::swap(&mut self.pending, &mut self.pixels);
+        }
+        self.frames += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        if self.frames > self.start {
+            self.write_pending()?;
+            self.start = self.frames;
+        }
+        match self.encoder.take() {
+            Some(encoder) => encoder.into_inner()?.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+fn gif_error(err: gif::EncodingError) -> io::Error {
+    match err {
+        gif::EncodingError::Io(err) => err,
+        err => io::Error::new(io::ErrorKind::InvalidData, err),
+    }
+}
+
+/// Streams raw YUV4MPEG2 video at 60 frames a second, which most video
+/// tools read directly, e.g. `ffmpeg -i out.y4m out.mp4`.
+pub struct Y4m<W: Write> {
+    out: W,
+    scale: u16,
+    // Y, Cb and Cr for each palette entry.
+    colors: [[u8; 3]; 4],
+    pixels: Vec<u8>,
+    planes: Vec<u8>,
+}
+
+impl<W: Write> Y4m<W> {
+    pub fn new(mut out: W, scale: u16, palette: Palette) -> io::Result<Self> {
+        let (width, height) = frame_size(scale);
+        writeln!(
+            out,
+            "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444",
+            width,
+            height,
+            Scheduler::FRAME_RATE
+        )?;
+        Ok(Y4m {
+            out,
+            scale,
+            colors: palette.map(ycbcr),
+            pixels: Vec::new(),
+            planes: Vec::new(),
+        })
+    }
+}
+
+// BT.601 studio swing, which is what players assume for Y4M.
+fn ycbcr([r, g, b]: [u8; 3]) -> [u8; 3] {
+    let (r, g, b) = (f64::from(r), f64::from(g), f64::from(b));
+    [
+        16.0 + (65.481 * r + 128.553 * g + 24.966 * b) / 255.0,
+        128.0 + (-37.797 * r - 74.203 * g + 112.0 * b) / 255.0,
+        128.0 + (112.0 * r - 93.786 * g - 18.214 * b) / 255.0,
+    ]
+    .map(|c| c.round() as u8)
+}
+
+impl<W: Write> FrameSink for Y4m<W> {
+    fn frame(&mut self, chip8: &Chip8) -> io::Result<()> {
+        capture(chip8, self.scale, &mut self.pixels);
+        self.planes.clear();
+        self.planes.extend_from_slice(b"FRAME\n");
+        for plane in 0..3 {
+            let colors = &self.colors;
+            self.planes
+                .extend(self.pixels.iter().map(|&pix| colors[pix as usize][plane]));
+        }
+        self.out.write_all(&self.planes)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Writes every frame to its own binary PPM file, numbered from zero: a
+/// `path` of `clip.ppm` gives `clip-000000.ppm`, `clip-000001.ppm` and so on.
+pub struct PpmSequence {
+    path: PathBuf,
+    scale: u16,
+    palette: Palette,
+    frames: u64,
+    pixels: Vec<u8>,
+}
+
+impl PpmSequence {
+    pub fn new(path: &Path, scale: u16, palette: Palette) -> Self {
+        PpmSequence {
+            path: path.to_path_buf(),
+            scale,
+            palette,
+            frames: 0,
+            pixels: Vec::new(),
+        }
+    }
+
+    fn frame_path(&self) -> PathBuf {
+        let stem = self.path.file_stem().unwrap_or_default().to_string_lossy();
+        self.path
+            .with_file_name(format!("{}-{:06}.ppm", stem, self.frames))
+    }
+}
+
+impl FrameSink for PpmSequence {
+    fn frame(&mut self, chip8: &Chip8) -> io::Result<()> {
+        capture(chip8, self.scale, &mut self.pixels);
+        let (width, height) = frame_size(self.scale);
+        let mut image = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+        image.extend(
+            self.pixels
+                .iter()
+                .flat_map(|&pix| self.palette[pix as usize]),
+        );
+        fs::write(self.frame_path(), image)?;
+        self.frames += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A machine showing one lit lo-res pixel, or with `clear` a blank screen.
+    fn screen(clear: bool) -> Chip8 {
+        let mut chip8 = Chip8::new();
+        // 00E0, or D011 drawing the top row of the 0 glyph at I = 0.
+        let rom: &[u8] = if clear { &[0x00, 0xE0] } else { &[0xD0, 0x11] };
+        chip8.load_rom(rom).unwrap();
+        chip8.step().unwrap();
+        chip8
+    }
+
+    #[test]
+    fn captures_lores_pixels_twice_as_big() {
+        let mut pixels = Vec::new();
+        capture(&screen(false), 1, &mut pixels);
+        assert_eq!(pixels.len(), 128 * 64);
+        // The 0 glyph's top row is 11110000.
+        assert_eq!(&pixels[..10], &[1, 1, 1, 1, 1, 1, 1, 1, 0, 0]);
+        assert_eq!(&pixels[128..138], &[1, 1, 1, 1, 1, 1, 1, 1, 0, 0]);
+        assert!(pixels[256..].iter().all(|&pix| pix == 0));
+    }
+
+    #[test]
+    fn gif_merges_frames() {
+        let (lit, blank) = (screen(false), screen(true));
+        let mut data = Vec::new();
+        let mut gif = Gif::new(&mut data, 1, DEFAULT_PALETTE).unwrap();
+        // Half a second lit, a one frame blip, then another half second.
+        for &(chip8, count) in &[(&lit, 30), (&blank, 1), (&lit, 29)] {
+            for _ in 0..count {
+                gif.frame(chip8).unwrap();
+            }
+        }
+        gif.finish().unwrap();
+        drop(gif);
+
+        let mut decoder = gif::DecodeOptions::new().read_info(&data[..]).unwrap();
+        assert_eq!((decoder.width(), decoder.height()), (128, 64));
+        let mut delays = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            assert_eq!(frame.buffer[0], 1);
+            delays.push(frame.delay);
+        }
+        assert_eq!(delays, [50, 50]);
+    }
+
+    #[test]
+    fn y4m_frames() {
+        let mut data = Vec::new();
+        let mut y4m = Y4m::new(&mut data, 1, DEFAULT_PALETTE).unwrap();
+        y4m.frame(&screen(false)).unwrap();
+        y4m.finish().unwrap();
+        drop(y4m);
+
+        let header = b"YUV4MPEG2 W128 H64 F60:1 Ip A1:1 C444\nFRAME\n";
+        assert_eq!(&data[..header.len()], &header[..]);
+        let planes = &data[header.len()..];
+        assert_eq!(planes.len(), 3 * 128 * 64);
+        // White and black luma, neutral chroma.
+        assert_eq!((planes[0], planes[8]), (235, 16));
+        assert!(planes[128 * 64..].iter().all(|&c| c == 128));
+    }
+}
@@ -0,0 +1,737 @@
+pub(crate) mod state;
+#[cfg(test)]
+mod tests;
+
+use crate::error::Chip8Error;
+use crate::keypad::Keypad;
+use crate::quirks::{MemoryIncrement, Quirks};
+use crate::random::Random;
+use std::fmt::{Display, Error, Formatter};
+use std::ops::Range;
+
+/// A complete CHIP-8 machine: registers, memory, framebuffer and keypad.
+pub struct Chip8 {
+    pc: u16,
+    i: u16,
+    v: [u8; Chip8::NUM_REGISTERS],
+    delay_timer: u8,
+    sound_timer: u8,
+    stack: [u16; Chip8::MAX_STACK],
+    sp: usize,
+    ram: Vec<u8>,
+    vram: Vec<u8>,
+    hires: bool,
+    planes: u8,
+    audio_pattern: [u8; Chip8::AUDIO_PATTERN_SIZE],
+    pitch: u8,
+    xo_chip: bool,
+    quirks: Quirks,
+    rpl: [u8; Chip8::NUM_REGISTERS],
+    keypad: Keypad,
+    key_wait: Option<usize>,
+    halted: bool,
+    cycles: u64,
+    random: Random,
+    dirty: bool,
+}
+
+impl Chip8 {
+    pub(crate) const PC_START: usize = 0x200;
+    const OP_SIZE: u16 = 2;
+    const RAM_SIZE: usize = 4096;
+    const XO_RAM_SIZE: usize = 65536;
+    const AUDIO_PATTERN_SIZE: usize = 16;
+    pub const LORES_WIDTH: usize = 64;
+    pub const LORES_HEIGHT: usize = 32;
+    pub const HIRES_WIDTH: usize = 128;
+    pub const HIRES_HEIGHT: usize = 64;
+    const NUM_REGISTERS: usize = 16;
+    const MAX_STACK: usize = 24;
+    const FONT_SET: [u8; 80] = [
+        0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+        0x20, 0x60, 0x20, 0x20, 0x70, // 1
+        0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+        0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+        0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+        0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+        0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+        0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+        0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+        0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+        0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+        0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+        0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+        0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+        0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+        0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+    ];
+    const BIG_FONT_START: usize = Chip8::FONT_SET.len();
+    const BIG_FONT_SET: [u8; 160] = [
+        0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+        0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+        0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+        0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+        0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+        0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+        0x3E, 0x7C, 0xE0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+        0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+        0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+        0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+        0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+        0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+        0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+        0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+        0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+        0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+    ];
+
+    /// A CHIP-8 machine that also understands the SUPER-CHIP instructions.
+    pub fn new() -> Self {
+        let mut chip8 = Chip8 {
+            pc: Chip8::PC_START as u16,
+            i: 0,
+            v: [0; Chip8::NUM_REGISTERS],
+            delay_timer: 0,
+            sound_timer: 0,
+            stack: [0; Chip8::MAX_STACK],
+            sp: 0,
+            ram: vec![0; Chip8::RAM_SIZE],
+            vram: vec![0; Chip8::LORES_WIDTH * Chip8::LORES_HEIGHT],
+            hires: false,
+            planes: 0x1,
+            audio_pattern: [0; Chip8::AUDIO_PATTERN_SIZE],
+            pitch: 64,
+            xo_chip: false,
+            quirks: Quirks::default(),
+            rpl: [0; Chip8::NUM_REGISTERS],
+            keypad: Keypad::new(),
+            key_wait: None,
+            halted: false,
+            cycles: 0,
+            random: Random::new(rand::random()),
+            dirty: true,
+        };
+        chip8.ram[..Chip8::FONT_SET.len()].copy_from_slice(&Chip8::FONT_SET);
+        chip8.ram[Chip8::BIG_FONT_START..][..Chip8::BIG_FONT_SET.len()]
+            .copy_from_slice(&Chip8::BIG_FONT_SET);
+        chip8
+    }
+
+    /// An XO-CHIP machine: 64K of memory, two bitplanes and pattern audio on
+    /// top of the SUPER-CHIP instruction set.
+    pub fn new_xo_chip() -> Self {
+        let mut chip8 = Chip8::new();
+        chip8.ram.resize(Chip8::XO_RAM_SIZE, 0);
+        chip8.xo_chip = true;
+        chip8.quirks = Quirks::XO_CHIP;
+        chip8
+    }
+
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), Chip8Error> {
+        let max = self.ram.len() - Chip8::PC_START;
+        if rom.len() > max {
+            return Err(Chip8Error::RomTooLarge {
+                size: rom.len(),
+                max,
+            });
+        }
+        self.ram[Chip8::PC_START..][..rom.len()].copy_from_slice(rom);
+        Ok(())
+    }
+
+    /// Puts the machine back in its power-on state with `rom` loaded, keeping
+    /// its XO-CHIP mode, quirks and random seed.
+    pub fn reset(&mut self, rom: &[u8]) -> Result<(), Chip8Error> {
+        let mut chip8 = if self.xo_chip {
+            Chip8::new_xo_chip()
+        } else {
+            Chip8::new()
+        };
+        chip8.quirks = self.quirks;
+        chip8.set_seed(self.seed());
+        chip8.load_rom(rom)?;
+        *self = chip8;
+        Ok(())
+    }
+
+    /// Executes a single instruction, unless an FX0A is still waiting for a key
+    /// or the program has exited.
+    pub fn step(&mut self) -> Result<(), Chip8Error> {
+        if !self.halted && !self.wait_for_key() {
+            let op = self.fetch_op()?;
+            self.cycles += 1;
+            self.decode_op(op)?;
+        }
+        Ok(())
+    }
+
+    /// Runs one 60 Hz frame: up to `instructions` instructions, stopping early
+    /// if the program exits, followed by a single timer tick.
+    pub fn run_frame(&mut self, instructions: u32) -> Result<(), Chip8Error> {
+        for _ in 0..instructions {
+            if self.halted {
+                break;
+            }
+            self.step()?;
+        }
+        self.tick_timers();
+        Ok(())
+    }
+
+    /// Counts the delay and sound timers down by one 60 Hz tick.
+    pub fn tick_timers(&mut self) {
+        if self.delay_timer > 0 {
+            self.delay_timer -= 1;
+        }
+        if self.sound_timer > 0 {
+            self.sound_timer -= 1;
+        }
+    }
+
+    /// One byte per pixel, row-major, `width()` by `height()`. Bit 0 of each
+    /// pixel is the first bitplane and bit 1 the second, so values range from
+    /// 0 to 3 in XO-CHIP mode and are 0 or 1 otherwise.
+    pub fn framebuffer(&self) -> &[u8] {
+        &self.vram
+    }
+
+    /// True if the screen may have changed since the last call. Front ends
+    /// can skip redrawing frames where it returns false.
+    pub fn take_dirty(&mut self) -> bool {
+        std::mem::replace(&mut self.dirty, false)
+    }
+
+    pub fn width(&self) -> usize {
+        if self.hires {
+            Chip8::HIRES_WIDTH
+        } else {
+            Chip8::LORES_WIDTH
+        }
+    }
+
+    pub fn height(&self) -> usize {
+        if self.hires {
+            Chip8::HIRES_HEIGHT
+        } else {
+            Chip8::LORES_HEIGHT
+        }
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    pub fn is_xo_chip(&self) -> bool {
+        self.xo_chip
+    }
+
+    /// The 128 one-bit samples loaded by F002, most significant bit first.
+    pub fn audio_pattern(&self) -> &[u8] {
+        &self.audio_pattern
+    }
+
+    /// Playback rate of the audio pattern in samples per second, set by FX3A.
+    pub fn audio_rate(&self) -> f64 {
+        4000.0 * 2f64.powf((f64::from(self.pitch) - 64.0) / 48.0)
+    }
+
+    /// True once the program has executed 00FD.
+    pub fn halted(&self) -> bool {
+        self.halted
+    }
+
+    /// The seed CXNN's random numbers were drawn from since power on or the
+    /// last `set_seed`. New machines pick one at random.
+    pub fn seed(&self) -> u64 {
+        self.random.seed()
+    }
+
+    /// Restarts the random numbers from `seed`. A program given the same seed
+    /// and the same input draws the same numbers every run.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.random = Random::new(seed);
+    }
+
+    /// Number of instructions fetched since power on, including one that
+    /// failed to execute.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// True while an FX0A is blocked waiting for a key to be released, and
+    /// no release is pending that would let it continue.
+    pub fn is_waiting_for_key(&self) -> bool {
+        self.key_wait.is_some() && !self.keypad.has_released()
+    }
+
+    /// True if the instruction at PC is a jump to itself, the usual way for
+    /// a program to stop without 00FD.
+    pub fn is_spinning(&self) -> bool {
+        // 1NNN can't reach the XO-CHIP memory above 0xFFF.
+        if self.pc > 0xFFF {
+            return false;
+        }
+        let pc = self.pc as usize;
+        match self.ram.get(pc..pc + 2) {
+            Some(&[b1, b2]) => u16::from(b1) << 8 | u16::from(b2) == 0x1000 | self.pc,
+            _ => false,
+        }
+    }
+
+    pub fn set_key(&mut self, key: u8, pressed: bool) {
+        if pressed {
+            self.keypad.press(key);
+        } else {
+            self.keypad.release(key);
+        }
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn i(&self) -> u16 {
+        self.i
+    }
+
+    /// Registers V0 through VF.
+    pub fn v(&self) -> &[u8] {
+        &self.v
+    }
+
+    /// Return addresses of the active calls, outermost first.
+    pub fn stack(&self) -> &[u16] {
+        &self.stack[..self.sp]
+    }
+
+    pub fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    pub fn is_key_down(&self, key: u8) -> bool {
+        self.keypad.is_down(key)
+    }
+
+    // Returns true while an FX0A is still blocked waiting for a key release.
+    fn wait_for_key(&mut self) -> bool {
+        match self.key_wait {
+            Some(vx) => match self.keypad.take_released() {
+                Some(key) => {
+                    self.v[vx] = key;
+                    self.key_wait = None;
+                    false
+                }
+                None => true,
+            },
+            None => false,
+        }
+    }
+
+    fn fetch_op(&mut self) -> Result<(u8, usize, usize, usize), Chip8Error> {
+        let pc = self.pc as usize;
+        if pc + 1 >= self.ram.len() {
+            return Err(Chip8Error::PcOutOfBounds { pc: self.pc });
+        }
+        let op = Chip8::split_op(self.ram[pc], self.ram[pc + 1]);
+
+        // XO-CHIP programs can fill memory, running off the end wraps to 0.
+        self.pc = self.pc.wrapping_add(Chip8::OP_SIZE);
+
+        Ok(op)
+    }
+
+    // Splits an instruction into its four nibbles, widening the three operand
+    // nibbles for use as register indices.
+    pub(crate) fn split_op(b1: u8, b2: u8) -> (u8, usize, usize, usize) {
+        (
+            (b1 & 0xF0) >> 4,
+            (b1 & 0x0F) as usize,
+            ((b2 & 0xF0) >> 4) as usize,
+            (b2 & 0x0F) as usize,
+        )
+    }
+
+    fn decode_op(&mut self, op: (u8, usize, usize, usize)) -> Result<(), Chip8Error> {
+        let pc = self.pc.wrapping_sub(Chip8::OP_SIZE);
+        let opcode = Chip8::op_u16(op);
+        let out_of_bounds = |addr| Chip8Error::MemoryOutOfBounds { pc, opcode, addr };
+
+        match op {
+            (0x0, 0x0, 0xC, n) => self.scroll(0, n as isize),
+            (0x0, 0x0, 0xD, n) if self.xo_chip => self.scroll(0, -(n as isize)),
+            (0x0, 0x0, 0xE, 0x0) => {
+                let planes = self.planes;
+                self.vram.iter_mut().for_each(|pix| *pix &= !planes);
+                self.dirty = true;
+            }
+            (0x0, 0x0, 0xE, 0xE) => {
+                if self.sp == 0 {
+                    return Err(Chip8Error::StackUnderflow { pc, opcode });
+                }
+                self.sp -= 1;
+                self.pc = self.stack[self.sp];
+            }
+            (0x0, 0x0, 0xF, 0xB) => self.scroll(4, 0),
+            (0x0, 0x0, 0xF, 0xC) => self.scroll(-4, 0),
+            (0x0, 0x0, 0xF, 0xD) => self.halted = true,
+            (0x0, 0x0, 0xF, 0xE) => self.set_hires(false),
+            (0x0, 0x0, 0xF, 0xF) => self.set_hires(true),
+            (0x0, _, _, _) => return Err(Chip8Error::MachineRoutine { pc, opcode }),
+            (0x1, n1, n2, n3) => self.pc = Chip8::n3u16(n1, n2, n3),
+            (0x2, n1, n2, n3) => {
+                if self.sp == Chip8::MAX_STACK {
+                    return Err(Chip8Error::StackOverflow { pc, opcode });
+                }
+                self.stack[self.sp] = self.pc;
+                self.sp += 1;
+                self.pc = Chip8::n3u16(n1, n2, n3);
+            }
+            (0x3, vx, n1, n2) => {
+                if self.v[vx] == Chip8::n2u8(n1, n2) {
+                    self.skip();
+                }
+            }
+            (0x4, vx, n1, n2) => {
+                if self.v[vx] != Chip8::n2u8(n1, n2) {
+                    self.skip();
+                }
+            }
+            (0x5, vx, vy, 0x0) => {
+                if self.v[vx] == self.v[vy] {
+                    self.skip();
+                }
+            }
+            (0x5, vx, vy, 0x2) if self.xo_chip => {
+                let regs = Chip8::reg_range(vx, vy);
+                let range = self.i_range(regs.len()).map_err(out_of_bounds)?;
+                for (addr, reg) in range.zip(regs) {
+                    self.ram[addr] = self.v[reg];
+                }
+            }
+            (0x5, vx, vy, 0x3) if self.xo_chip => {
+                let regs = Chip8::reg_range(vx, vy);
+                let range = self.i_range(regs.len()).map_err(out_of_bounds)?;
+                for (addr, reg) in range.zip(regs) {
+                    self.v[reg] = self.ram[addr];
+                }
+            }
+            (0x6, vx, n1, n2) => self.v[vx] = Chip8::n2u8(n1, n2),
+            (0x7, vx, n1, n2) => self.v[vx] = self.v[vx].wrapping_add(Chip8::n2u8(n1, n2)),
+            (0x8, vx, vy, 0x0) => self.v[vx] = self.v[vy],
+            (0x8, vx, vy, 0x1) => {
+                self.v[vx] |= self.v[vy];
+                self.reset_vf_after_logic();
+            }
+            (0x8, vx, vy, 0x2) => {
+                self.v[vx] &= self.v[vy];
+                self.reset_vf_after_logic();
+            }
+            (0x8, vx, vy, 0x3) => {
+                self.v[vx] ^= self.v[vy];
+                self.reset_vf_after_logic();
+            }
+            (0x8, vx, vy, 0x4) => {
+                let (res, carry) = self.v[vx].overflowing_add(self.v[vy]);
+                self.set_with_flag(vx, res, carry as u8);
+            }
+            (0x8, vx, vy, 0x5) => {
+                let (res, borrow) = self.v[vx].overflowing_sub(self.v[vy]);
+                self.set_with_flag(vx, res, !borrow as u8);
+            }
+            (0x8, vx, vy, 0x6) => {
+                let src = self.v[self.shift_source(vx, vy)];
+                self.set_with_flag(vx, src >> 1, src & 0x01);
+            }
+            (0x8, vx, vy, 0x7) => {
+                let (res, borrow) = self.v[vy].overflowing_sub(self.v[vx]);
+                self.set_with_flag(vx, res, !borrow as u8);
+            }
+            (0x8, vx, vy, 0xE) => {
+                let src = self.v[self.shift_source(vx, vy)];
+                self.set_with_flag(vx, src << 1, src >> 7);
+            }
+            (0x9, vx, vy, 0x0) => {
+                if self.v[vx] != self.v[vy] {
+                    self.skip();
+                }
+            }
+            (0xA, n1, n2, n3) => self.i = Chip8::n3u16(n1, n2, n3),
+            (0xB, n1, n2, n3) => {
+                let offset = if self.quirks.jump_uses_vx {
+                    self.v[n1]
+                } else {
+                    self.v[0]
+                };
+                self.pc = Chip8::n3u16(n1, n2, n3) + u16::from(offset);
+            }
+            (0xC, vx, n1, n2) => self.v[vx] = self.random.next_u8() & Chip8::n2u8(n1, n2),
+            (0xD, vx, vy, n) => {
+                // DXY0 draws a 16x16 sprite. With both XO-CHIP planes selected
+                // the second plane's sprite data follows the first's.
+                let (rows, cols) = if n == 0 { (16, 16) } else { (n, 8) };
+                let size = rows * cols / 8;
+                let planes: Vec<u8> = [0x1, 0x2]
+                    .iter()
+                    .cloned()
+                    .filter(|plane| self.planes & plane != 0)
+                    .collect();
+                let sprite = self.i_range(size * planes.len()).map_err(out_of_bounds)?;
+                let x = self.v[vx] as usize;
+                let y = self.v[vy] as usize;
+
+                let mut collided = 0;
+                for (n, &plane) in planes.iter().enumerate() {
+                    let start = sprite.start + n * size;
+                    let sprite = start..start + size;
+                    collided = collided.max(self.draw_sprite(x, y, sprite, cols, plane));
+                }
+                self.v[0xF] = if self.hires && !self.xo_chip {
+                    // SUPER-CHIP counts the rows that collided or were clipped
+                    // off the bottom of the hi-res screen.
+                    let clipped = if self.quirks.clip_sprites {
+                        (y % self.height() + rows).saturating_sub(self.height())
+                    } else {
+                        0
+                    };
+                    collided + clipped as u8
+                } else {
+                    collided.min(1)
+                };
+                self.dirty = true;
+            }
+            (0xE, vx, 0x9, 0xE) => {
+                if self.keypad.is_down(self.v[vx]) {
+                    self.skip();
+                }
+            }
+            (0xE, vx, 0xA, 0x1) => {
+                if !self.keypad.is_down(self.v[vx]) {
+                    self.skip();
+                }
+            }
+            (0xF, 0x0, 0x0, 0x0) if self.xo_chip => {
+                let next = self.pc as usize;
+                if next + 1 >= self.ram.len() {
+                    return Err(Chip8Error::PcOutOfBounds { pc: self.pc });
+                }
+                self.i = u16::from(self.ram[next]) << 8 | u16::from(self.ram[next + 1]);
+                self.pc = self.pc.wrapping_add(Chip8::OP_SIZE);
+            }
+            (0xF, n, 0x0, 0x1) if self.xo_chip => self.planes = n as u8 & 0x3,
+            (0xF, 0x0, 0x0, 0x2) if self.xo_chip => {
+                let range = self
+                    .i_range(Chip8::AUDIO_PATTERN_SIZE)
+                    .map_err(out_of_bounds)?;
+                self.audio_pattern.copy_from_slice(&self.ram[range]);
+            }
+            (0xF, vx, 0x0, 0x7) => self.v[vx] = self.delay_timer,
+            (0xF, vx, 0x0, 0xA) => {
+                self.keypad.take_released();
+                self.key_wait = Some(vx);
+            }
+            (0xF, vx, 0x1, 0x5) => self.delay_timer = self.v[vx],
+            (0xF, vx, 0x1, 0x8) => self.sound_timer = self.v[vx],
+            (0xF, vx, 0x1, 0xE) => self.i = self.i.wrapping_add(u16::from(self.v[vx])),
+            (0xF, vx, 0x2, 0x9) => self.i = u16::from(self.v[vx] & 0x0F) * 5,
+            (0xF, vx, 0x3, 0x0) => {
+                self.i = (Chip8::BIG_FONT_START + (self.v[vx] & 0x0F) as usize * 10) as u16
+            }
+            (0xF, vx, 0x3, 0x3) => {
+                let v = self.v[vx];
+                let bcd = [v / 100, (v / 10) % 10, v % 10];
+                let range = self.i_range(bcd.len()).map_err(out_of_bounds)?;
+                self.ram[range].copy_from_slice(&bcd);
+            }
+            (0xF, vx, 0x5, 0x5) => {
+                let range = self.i_range(vx + 1).map_err(out_of_bounds)?;
+                self.ram[range].copy_from_slice(&self.v[0..=vx]);
+                self.increment_i_after_memory(vx);
+            }
+
+            (0xF, vx, 0x6, 0x5) => {
+                let range = self.i_range(vx + 1).map_err(out_of_bounds)?;
+                self.v[0..=vx].copy_from_slice(&self.ram[range]);
+                self.increment_i_after_memory(vx);
+            }
+
+            (0xF, vx, 0x3, 0xA) if self.xo_chip => self.pitch = self.v[vx],
+            (0xF, vx, 0x7, 0x5) => self.rpl[0..=vx].copy_from_slice(&self.v[0..=vx]),
+            (0xF, vx, 0x8, 0x5) => self.v[0..=vx].copy_from_slice(&self.rpl[0..=vx]),
+
+            _ => return Err(Chip8Error::InvalidOpcode { pc, opcode }),
+        }
+        Ok(())
+    }
+
+    // Skips the next instruction, which is four bytes long if it is an
+    // XO-CHIP F000 NNNN.
+    fn skip(&mut self) {
+        let next = self.pc as usize;
+        if self.xo_chip && self.ram.get(next..next + 2) == Some(&[0xF0, 0x00]) {
+            self.pc = self.pc.wrapping_add(Chip8::OP_SIZE);
+        }
+        self.pc = self.pc.wrapping_add(Chip8::OP_SIZE);
+    }
+
+    // VF is written after the result, so the flag wins when X is F.
+    fn set_with_flag(&mut self, vx: usize, res: u8, flag: u8) {
+        self.v[vx] = res;
+        self.v[0xF] = flag;
+    }
+
+    fn shift_source(&self, vx: usize, vy: usize) -> usize {
+        if self.quirks.shift_uses_vy {
+            vy
+        } else {
+            vx
+        }
+    }
+
+    fn reset_vf_after_logic(&mut self) {
+        if self.quirks.logic_resets_vf {
+            self.v[0xF] = 0;
+        }
+    }
+
+    fn increment_i_after_memory(&mut self, vx: usize) {
+        let increment = match self.quirks.memory_increment {
+            MemoryIncrement::None => 0,
+            MemoryIncrement::X => vx as u16,
+            MemoryIncrement::XPlusOne => vx as u16 + 1,
+        };
+        self.i = self.i.wrapping_add(increment);
+    }
+
+    // XORs a `cols` pixel wide sprite onto one bitplane of the screen. The
+    // sprite's origin always wraps, the pixels past the edges either wrap or
+    // are clipped depending on the quirks. Returns the number of rows in which
+    // a lit pixel was erased.
+    fn draw_sprite(
+        &mut self,
+        x: usize,
+        y: usize,
+        sprite: Range<usize>,
+        cols: usize,
+        plane: u8,
+    ) -> u8 {
+        let (width, height) = (self.width(), self.height());
+        let (x, y) = (x % width, y % height);
+        let clip = self.quirks.clip_sprites;
+        let mut collided = 0;
+        for (h, row) in self.ram[sprite].chunks(cols / 8).enumerate() {
+            let mut collision = false;
+            for w in 0..cols {
+                if (row[w / 8] >> (7 - w % 8)) & 0x01 == 0 {
+                    continue;
+                }
+                if clip && (x + w >= width || y + h >= height) {
+                    continue;
+                }
+                let pos = (x + w) % width + ((y + h) % height) * width;
+                if self.vram[pos] & plane != 0 {
+                    collision = true;
+                }
+                self.vram[pos] ^= plane;
+            }
+            collided += collision as u8;
+        }
+        collided
+    }
+
+    // Shifts the selected bitplanes by (dx, dy) pixels, filling with blank
+    // pixels.
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let (width, height) = (self.width() as isize, self.height() as isize);
+        let planes = self.planes;
+        let mut scrolled: Vec<u8> = self.vram.iter().map(|pix| pix & !planes).collect();
+        for y in 0..height {
+            for x in 0..width {
+                let (from_x, from_y) = (x - dx, y - dy);
+                if from_x >= 0 && from_x < width && from_y >= 0 && from_y < height {
+                    scrolled[(x + y * width) as usize] |=
+                        self.vram[(from_x + from_y * width) as usize] & planes;
+                }
+            }
+        }
+        self.vram = scrolled;
+        self.dirty = true;
+    }
+
+    fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+        self.vram = vec![0; self.width() * self.height()];
+        self.dirty = true;
+    }
+
+    // The `len` bytes of RAM starting at I, or the last address that falls
+    // outside of memory.
+    fn i_range(&self, len: usize) -> Result<Range<usize>, usize> {
+        let start = self.i as usize;
+        if start + len > self.ram.len() {
+            Err(start + len - 1)
+        } else {
+            Ok(start..start + len)
+        }
+    }
+
+    // Registers X through Y inclusive, in descending order if X > Y.
+    fn reg_range(vx: usize, vy: usize) -> Vec<usize> {
+        if vx <= vy {
+            (vx..=vy).collect()
+        } else {
+            (vy..=vx).rev().collect()
+        }
+    }
+
+    pub(crate) fn n2u8(n1: usize, n2: usize) -> u8 {
+        (n1 << 4 | n2) as u8
+    }
+    pub(crate) fn n3u16(n1: usize, n2: usize, n3: usize) -> u16 {
+        (n1 << 8 | n2 << 4 | n3) as u16
+    }
+    pub(crate) fn op_u16(op: (u8, usize, usize, usize)) -> u16 {
+        u16::from(op.0) << 12 | Chip8::n3u16(op.1, op.2, op.3)
+    }
+}
+
+impl Display for Chip8 {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        writeln!(
+            f,
+            "PC: {:03X}  I: {:03X}  DT: {:02X}  ST: {:02X}",
+            self.pc, self.i, self.delay_timer, self.sound_timer
+        )?;
+        for (n, v) in self.v.iter().enumerate() {
+            write!(
+                f,
+                "V{:X}: {:02X}{}",
+                n,
+                v,
+                if n % 8 == 7 { "\n" } else { "  " }
+            )?;
+        }
+        write!(f, "Stack:")?;
+        for addr in self.stack() {
+            write!(f, " {:03X}", addr)?;
+        }
+        writeln!(f)
+    }
+}
+
+impl Default for Chip8 {
+    fn default() -> Self {
+        Chip8::new()
+    }
+}
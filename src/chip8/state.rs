@@ -0,0 +1,288 @@
+//! Binary save states.
+//!
+//! A state starts with a four byte magic and a format version, followed by
+//! every field of the machine in declaration order. Multi-byte integers are
+//! big-endian and variable-length buffers are prefixed with their length.
+
+use super::Chip8;
+use crate::error::StateError;
+use crate::keypad::Keypad;
+use crate::quirks::{MemoryIncrement, Quirks};
+use crate::random::Random;
+
+const MAGIC: &[u8; 4] = b"RC8S";
+const VERSION: u8 = 3;
+// Stored in place of an absent `Option<u8>`.
+const NONE: u8 = 0xFF;
+
+impl Chip8 {
+    /// Serializes the complete machine state.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut w = Writer(Vec::with_capacity(self.ram.len() + self.vram.len() + 256));
+        w.bytes(MAGIC);
+        w.u8(VERSION);
+
+        w.u16(self.pc);
+        w.u16(self.i);
+        w.bytes(&self.v);
+        w.u8(self.delay_timer);
+        w.u8(self.sound_timer);
+        for &addr in self.stack.iter() {
+            w.u16(addr);
+        }
+        w.u8(self.sp as u8);
+        w.buffer(&self.ram);
+        w.buffer(&self.vram);
+        w.bool(self.hires);
+        w.u8(self.planes);
+        w.bytes(&self.audio_pattern);
+        w.u8(self.pitch);
+        w.bool(self.xo_chip);
+        w.quirks(self.quirks);
+        w.bytes(&self.rpl);
+        let (down, released) = self.keypad.snapshot();
+        w.u16(down);
+        w.u8(released.unwrap_or(NONE));
+        w.u8(self.key_wait.map_or(NONE, |vx| vx as u8));
+        w.bool(self.halted);
+        w.u64(self.cycles);
+        w.u64(self.random.seed());
+        w.u64(self.random.state());
+        w.0
+    }
+
+    /// Replaces the machine with a state produced by `save_state`. The
+    /// machine is left untouched if the state is rejected.
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        let mut r = Reader(state);
+        if r.bytes(MAGIC.len())? != MAGIC {
+            return Err(StateError::BadMagic);
+        }
+        let version = r.u8()?;
+        if version != VERSION {
+            return Err(StateError::UnsupportedVersion(version));
+        }
+
+        let mut chip8 = Chip8::new();
+        chip8.pc = r.u16()?;
+        chip8.i = r.u16()?;
+        chip8.v.copy_from_slice(r.bytes(Chip8::NUM_REGISTERS)?);
+        chip8.delay_timer = r.u8()?;
+        chip8.sound_timer = r.u8()?;
+        for addr in chip8.stack.iter_mut() {
+            *addr = r.u16()?;
+        }
+        chip8.sp = r.u8()? as usize;
+        chip8.ram = r.buffer()?.to_vec();
+        chip8.vram = r.buffer()?.to_vec();
+        chip8.hires = r.bool()?;
+        chip8.planes = r.u8()?;
+        chip8
+            .audio_pattern
+            .copy_from_slice(r.bytes(Chip8::AUDIO_PATTERN_SIZE)?);
+        chip8.pitch = r.u8()?;
+        chip8.xo_chip = r.bool()?;
+        chip8.quirks = r.quirks()?;
+        chip8.rpl.copy_from_slice(r.bytes(Chip8::NUM_REGISTERS)?);
+        let down = r.u16()?;
+        let released = r.option()?;
+        chip8.keypad = Keypad::restore(down, released);
+        chip8.key_wait = r.option()?.map(usize::from);
+        chip8.halted = r.bool()?;
+        chip8.cycles = r.u64()?;
+        let seed = r.u64()?;
+        chip8.random = Random::restore(seed, r.u64()?);
+
+        let ram_size = if chip8.xo_chip {
+            Chip8::XO_RAM_SIZE
+        } else {
+            Chip8::RAM_SIZE
+        };
+        if !r.0.is_empty()
+            || chip8.sp > Chip8::MAX_STACK
+            || chip8.ram.len() != ram_size
+            || chip8.vram.len() != chip8.width() * chip8.height()
+            || chip8.planes > 0x3
+            || released.is_some_and(|key| key as usize >= Keypad::NUM_KEYS)
+            || chip8.key_wait.is_some_and(|vx| vx >= Chip8::NUM_REGISTERS)
+        {
+            return Err(StateError::Corrupt);
+        }
+
+        *self = chip8;
+        Ok(())
+    }
+}
+
+// Encoding shared with the other binary formats, such as movies.
+pub(crate) struct Writer(pub Vec<u8>);
+
+impl Writer {
+    pub fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    pub fn u16(&mut self, value: u16) {
+        self.0.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.0.extend_from_slice(bytes);
+    }
+
+    pub fn buffer(&mut self, bytes: &[u8]) {
+        self.0
+            .extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+        self.bytes(bytes);
+    }
+
+    pub fn quirks(&mut self, quirks: Quirks) {
+        self.bool(quirks.shift_uses_vy);
+        self.u8(match quirks.memory_increment {
+            MemoryIncrement::None => 0,
+            MemoryIncrement::X => 1,
+            MemoryIncrement::XPlusOne => 2,
+        });
+        self.bool(quirks.jump_uses_vx);
+        self.bool(quirks.clip_sprites);
+        self.bool(quirks.logic_resets_vf);
+    }
+}
+
+pub(crate) struct Reader<'a>(pub &'a [u8]);
+
+impl<'a> Reader<'a> {
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], StateError> {
+        if self.0.len() < len {
+            return Err(StateError::Truncated);
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16, StateError> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, StateError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.bytes(8)?);
+        Ok(u64::from_be_bytes(bytes))
+    }
+
+    pub fn bool(&mut self) -> Result<bool, StateError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(StateError::Corrupt),
+        }
+    }
+
+    pub fn option(&mut self) -> Result<Option<u8>, StateError> {
+        Ok(match self.u8()? {
+            NONE => None,
+            value => Some(value),
+        })
+    }
+
+    pub fn buffer(&mut self) -> Result<&'a [u8], StateError> {
+        let len = self.bytes(4)?;
+        let len = u32::from_be_bytes([len[0], len[1], len[2], len[3]]);
+        self.bytes(len as usize)
+    }
+
+    pub fn quirks(&mut self) -> Result<Quirks, StateError> {
+        Ok(Quirks {
+            shift_uses_vy: self.bool()?,
+            memory_increment: match self.u8()? {
+                0 => MemoryIncrement::None,
+                1 => MemoryIncrement::X,
+                2 => MemoryIncrement::XPlusOne,
+                _ => return Err(StateError::Corrupt),
+            },
+            jump_uses_vx: self.bool()?,
+            clip_sprites: self.bool()?,
+            logic_resets_vf: self.bool()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An XO-CHIP machine in hi-res, holding a key while FX0A waits for one.
+    fn machine() -> Chip8 {
+        let mut chip8 = Chip8::new_xo_chip();
+        // 00FF, 6005, F10A
+        chip8
+            .load_rom(&[0x00, 0xFF, 0x60, 0x05, 0xF1, 0x0A])
+            .unwrap();
+        for _ in 0..3 {
+            chip8.step().unwrap();
+        }
+        chip8.set_key(0x7, true);
+        chip8
+    }
+
+    #[test]
+    fn round_trips() {
+        let state = machine().save_state();
+        let mut chip8 = Chip8::new();
+        chip8.load_state(&state).unwrap();
+
+        assert!(chip8.is_xo_chip());
+        assert_eq!((chip8.width(), chip8.height()), (128, 64));
+        assert_eq!(chip8.v[0], 5);
+        assert!(chip8.is_key_down(0x7));
+        assert_eq!(chip8.key_wait, Some(1));
+        assert_eq!(chip8.save_state(), state);
+    }
+
+    #[test]
+    fn rejects_bad_states() {
+        let state = machine().save_state();
+        let mut newer = state.clone();
+        newer[4] = VERSION + 1;
+        let mut trailing = state.clone();
+        trailing.push(0);
+        // Finds `planes` as the first byte that changes with it.
+        let mut other = machine();
+        other.planes = 0x2;
+        let planes = state
+            .iter()
+            .zip(other.save_state())
+            .position(|(&a, b)| a != b)
+            .unwrap();
+        let mut bad_planes = state.clone();
+        bad_planes[planes] = 4;
+
+        let mut chip8 = Chip8::new();
+        chip8.load_rom(&[0x12, 0x00]).unwrap();
+        let before = chip8.save_state();
+        for (data, err) in [
+            (&b"RC8M\x03"[..], StateError::BadMagic),
+            (&newer, StateError::UnsupportedVersion(VERSION + 1)),
+            (&state[..state.len() - 1], StateError::Truncated),
+            (&trailing, StateError::Corrupt),
+            (&bad_planes, StateError::Corrupt),
+        ] {
+            assert_eq!(chip8.load_state(data), Err(err));
+            assert_eq!(chip8.save_state(), before);
+        }
+    }
+}
@@ -0,0 +1,901 @@
+use super::*;
+
+const PC: u16 = Chip8::PC_START as u16;
+const NEXT: u16 = PC + 2;
+const SKIPPED: u16 = PC + 4;
+
+// Sets up a machine and executes a single opcode at PC_START through
+// `decode_op`, without the opcode having to be in RAM.
+struct Machine {
+    chip8: Chip8,
+}
+
+impl Machine {
+    fn new() -> Self {
+        Machine {
+            chip8: Chip8::new(),
+        }
+    }
+
+    fn xo_chip() -> Self {
+        Machine {
+            chip8: Chip8::new_xo_chip(),
+        }
+    }
+
+    fn quirks(mut self, quirks: Quirks) -> Self {
+        self.chip8.quirks = quirks;
+        self
+    }
+
+    fn hires(mut self) -> Self {
+        self.chip8.set_hires(true);
+        self
+    }
+
+    fn v(mut self, reg: usize, value: u8) -> Self {
+        self.chip8.v[reg] = value;
+        self
+    }
+
+    fn i(mut self, i: u16) -> Self {
+        self.chip8.i = i;
+        self
+    }
+
+    fn ram(mut self, addr: usize, bytes: &[u8]) -> Self {
+        self.chip8.ram[addr..addr + bytes.len()].copy_from_slice(bytes);
+        self
+    }
+
+    fn stack(mut self, stack: &[u16]) -> Self {
+        self.chip8.stack[..stack.len()].copy_from_slice(stack);
+        self.chip8.sp = stack.len();
+        self
+    }
+
+    fn delay_timer(mut self, value: u8) -> Self {
+        self.chip8.delay_timer = value;
+        self
+    }
+
+    fn seed(mut self, seed: u64) -> Self {
+        self.chip8.set_seed(seed);
+        self
+    }
+
+    fn key(mut self, key: u8) -> Self {
+        self.chip8.keypad.press(key);
+        self
+    }
+
+    fn pixel(mut self, x: usize, y: usize) -> Self {
+        let width = self.chip8.width();
+        self.chip8.vram[x + y * width] = 1;
+        self
+    }
+
+    fn try_exec(mut self, opcode: u16) -> Result<Chip8, Chip8Error> {
+        self.chip8.pc = PC + Chip8::OP_SIZE;
+        self.chip8
+            .decode_op(Chip8::split_op((opcode >> 8) as u8, opcode as u8))?;
+        Ok(self.chip8)
+    }
+
+    fn exec(self, opcode: u16) -> Chip8 {
+        self.try_exec(opcode).unwrap()
+    }
+}
+
+fn pixel(chip8: &Chip8, x: usize, y: usize) -> u8 {
+    chip8.vram[x + y * chip8.width()]
+}
+
+fn lit_pixels(chip8: &Chip8) -> usize {
+    chip8.vram.iter().filter(|&&pix| pix != 0).count()
+}
+
+#[test]
+fn op_0nnn_is_an_error() {
+    let err = Machine::new().try_exec(0x0123).err();
+    assert_eq!(
+        err,
+        Some(Chip8Error::MachineRoutine {
+            pc: PC,
+            opcode: 0x0123
+        })
+    );
+}
+
+#[test]
+fn op_00e0_clears_screen() {
+    let chip8 = Machine::new().pixel(0, 0).pixel(63, 31).exec(0x00E0);
+    assert_eq!(lit_pixels(&chip8), 0);
+    assert_eq!(chip8.pc, NEXT);
+}
+
+#[test]
+fn op_00ee_returns() {
+    let chip8 = Machine::new().stack(&[0x300, 0x400]).exec(0x00EE);
+    assert_eq!(chip8.pc, 0x400);
+    assert_eq!(chip8.stack(), &[0x300]);
+}
+
+#[test]
+fn op_00ee_underflow() {
+    let err = Machine::new().try_exec(0x00EE).err();
+    assert_eq!(
+        err,
+        Some(Chip8Error::StackUnderflow {
+            pc: PC,
+            opcode: 0x00EE
+        })
+    );
+}
+
+#[test]
+fn op_00cn_scrolls_down() {
+    let chip8 = Machine::new().pixel(3, 0).pixel(3, 30).exec(0x00C2);
+    assert_eq!(pixel(&chip8, 3, 2), 1);
+    assert_eq!(lit_pixels(&chip8), 1);
+
+    let chip8 = Machine::new().pixel(3, 0).exec(0x00C0);
+    assert_eq!(pixel(&chip8, 3, 0), 1);
+}
+
+#[test]
+fn op_00fb_00fc_scroll_sideways() {
+    let chip8 = Machine::new().pixel(0, 1).pixel(60, 1).exec(0x00FB);
+    assert_eq!(pixel(&chip8, 4, 1), 1);
+    assert_eq!(lit_pixels(&chip8), 1);
+
+    let chip8 = Machine::new().pixel(3, 1).pixel(63, 1).exec(0x00FC);
+    assert_eq!(pixel(&chip8, 59, 1), 1);
+    assert_eq!(lit_pixels(&chip8), 1);
+
+    let chip8 = Machine::new().hires().pixel(127, 63).exec(0x00FC);
+    assert_eq!(pixel(&chip8, 123, 63), 1);
+}
+
+#[test]
+fn op_00fd_halts() {
+    assert!(Machine::new().exec(0x00FD).halted());
+}
+
+#[test]
+fn op_00fe_00ff_switch_resolution_and_clear() {
+    let chip8 = Machine::new().pixel(63, 31).exec(0x00FF);
+    assert_eq!((chip8.width(), chip8.height()), (128, 64));
+    assert_eq!(chip8.vram.len(), 128 * 64);
+    assert_eq!(lit_pixels(&chip8), 0);
+
+    let chip8 = Machine::new().hires().pixel(127, 63).exec(0x00FE);
+    assert_eq!((chip8.width(), chip8.height()), (64, 32));
+    assert_eq!(chip8.vram.len(), 64 * 32);
+    assert_eq!(lit_pixels(&chip8), 0);
+}
+
+#[test]
+fn op_1nnn_jumps() {
+    let chip8 = Machine::new().exec(0x1345);
+    assert_eq!(chip8.pc, 0x345);
+}
+
+#[test]
+fn op_2nnn_calls() {
+    let chip8 = Machine::new().exec(0x2345);
+    assert_eq!(chip8.pc, 0x345);
+    assert_eq!(chip8.stack(), &[NEXT]);
+}
+
+#[test]
+fn op_2nnn_overflow() {
+    let full = [0x300; Chip8::MAX_STACK];
+    let err = Machine::new().stack(&full).try_exec(0x2345).err();
+    assert_eq!(
+        err,
+        Some(Chip8Error::StackOverflow {
+            pc: PC,
+            opcode: 0x2345
+        })
+    );
+}
+
+#[test]
+fn op_3xnn_skips_if_equal() {
+    assert_eq!(Machine::new().v(3, 0x42).exec(0x3342).pc, SKIPPED);
+    assert_eq!(Machine::new().v(3, 0x41).exec(0x3342).pc, NEXT);
+}
+
+#[test]
+fn op_4xnn_skips_if_not_equal() {
+    assert_eq!(Machine::new().v(3, 0x41).exec(0x4342).pc, SKIPPED);
+    assert_eq!(Machine::new().v(3, 0x42).exec(0x4342).pc, NEXT);
+}
+
+#[test]
+fn op_5xy0_skips_if_registers_equal() {
+    assert_eq!(Machine::new().v(1, 7).v(2, 7).exec(0x5120).pc, SKIPPED);
+    assert_eq!(Machine::new().v(1, 7).v(2, 8).exec(0x5120).pc, NEXT);
+}
+
+#[test]
+fn op_5xy2_5xy3_save_and_load_register_ranges() {
+    let chip8 = Machine::xo_chip()
+        .i(0x300)
+        .v(1, 1)
+        .v(2, 2)
+        .v(3, 3)
+        .exec(0x5132);
+    assert_eq!(&chip8.ram[0x300..0x304], &[1, 2, 3, 0]);
+    assert_eq!(chip8.i, 0x300);
+
+    // With X > Y the registers are copied in reverse order.
+    let chip8 = Machine::xo_chip()
+        .i(0x300)
+        .v(1, 1)
+        .v(2, 2)
+        .v(3, 3)
+        .exec(0x5312);
+    assert_eq!(&chip8.ram[0x300..0x303], &[3, 2, 1]);
+
+    let chip8 = Machine::xo_chip()
+        .i(0x300)
+        .ram(0x300, &[7, 8, 9])
+        .exec(0x5313);
+    assert_eq!(&chip8.v[1..4], &[9, 8, 7]);
+    let chip8 = Machine::xo_chip()
+        .i(0x300)
+        .ram(0x300, &[7, 8, 9])
+        .exec(0x5133);
+    assert_eq!(&chip8.v[1..4], &[7, 8, 9]);
+}
+
+#[test]
+fn op_5xy2_needs_xo_chip() {
+    let err = Machine::new().try_exec(0x5132).err();
+    assert_eq!(
+        err,
+        Some(Chip8Error::InvalidOpcode {
+            pc: PC,
+            opcode: 0x5132
+        })
+    );
+}
+
+#[test]
+fn op_6xnn_loads() {
+    let chip8 = Machine::new().exec(0x6A5C);
+    assert_eq!(chip8.v[0xA], 0x5C);
+}
+
+#[test]
+fn op_7xnn_adds_without_carry() {
+    let chip8 = Machine::new().v(1, 0x10).exec(0x7105);
+    assert_eq!(chip8.v[1], 0x15);
+
+    let chip8 = Machine::new().v(1, 0xFF).v(0xF, 0).exec(0x7102);
+    assert_eq!(chip8.v[1], 0x01);
+    assert_eq!(chip8.v[0xF], 0);
+}
+
+#[test]
+fn op_8xy0_copies() {
+    let chip8 = Machine::new().v(2, 0x99).exec(0x8120);
+    assert_eq!(chip8.v[1], 0x99);
+}
+
+#[test]
+fn op_8xy1_8xy2_8xy3_logic() {
+    let machine = || Machine::new().v(1, 0b1100).v(2, 0b1010).v(0xF, 5);
+    let or = machine().exec(0x8121);
+    let and = machine().exec(0x8122);
+    let xor = machine().exec(0x8123);
+    assert_eq!(or.v[1], 0b1110);
+    assert_eq!(and.v[1], 0b1000);
+    assert_eq!(xor.v[1], 0b0110);
+    assert_eq!(or.v[0xF], 5);
+}
+
+#[test]
+fn op_8xy1_resets_vf_on_vip() {
+    let chip8 = Machine::new()
+        .quirks(Quirks::COSMAC_VIP)
+        .v(0xF, 5)
+        .exec(0x8121);
+    assert_eq!(chip8.v[0xF], 0);
+}
+
+#[test]
+fn op_8xy4_adds_with_carry() {
+    let chip8 = Machine::new().v(1, 1).v(2, 2).exec(0x8124);
+    assert_eq!((chip8.v[1], chip8.v[0xF]), (3, 0));
+
+    let chip8 = Machine::new().v(1, 0xFF).v(2, 2).exec(0x8124);
+    assert_eq!((chip8.v[1], chip8.v[0xF]), (1, 1));
+}
+
+#[test]
+fn op_8xy4_vf_as_operand() {
+    // The flag is written after the result, so it wins.
+    let chip8 = Machine::new().v(0xF, 0xFF).v(1, 1).exec(0x8F14);
+    assert_eq!(chip8.v[0xF], 1);
+
+    let chip8 = Machine::new().v(1, 0xFF).v(0xF, 0xFF).exec(0x81F4);
+    assert_eq!((chip8.v[1], chip8.v[0xF]), (0xFE, 1));
+}
+
+#[test]
+fn op_8xy5_subtracts_with_borrow() {
+    let chip8 = Machine::new().v(1, 5).v(2, 3).exec(0x8125);
+    assert_eq!((chip8.v[1], chip8.v[0xF]), (2, 1));
+
+    let chip8 = Machine::new().v(1, 3).v(2, 5).exec(0x8125);
+    assert_eq!((chip8.v[1], chip8.v[0xF]), (0xFE, 0));
+
+    let chip8 = Machine::new().v(1, 5).v(2, 5).exec(0x8125);
+    assert_eq!((chip8.v[1], chip8.v[0xF]), (0, 1));
+}
+
+#[test]
+fn op_8xy5_above_127() {
+    let chip8 = Machine::new().v(1, 200).v(2, 100).exec(0x8125);
+    assert_eq!((chip8.v[1], chip8.v[0xF]), (100, 1));
+
+    let chip8 = Machine::new().v(1, 100).v(2, 200).exec(0x8125);
+    assert_eq!((chip8.v[1], chip8.v[0xF]), (156, 0));
+}
+
+#[test]
+fn op_8xy5_vf_as_operand() {
+    let chip8 = Machine::new().v(1, 5).v(0xF, 3).exec(0x81F5);
+    assert_eq!((chip8.v[1], chip8.v[0xF]), (2, 1));
+
+    let chip8 = Machine::new().v(0xF, 3).v(1, 5).exec(0x8F15);
+    assert_eq!(chip8.v[0xF], 0);
+}
+
+#[test]
+fn op_8xy6_shifts_right() {
+    let chip8 = Machine::new().v(1, 0b101).v(2, 0b1000).exec(0x8126);
+    assert_eq!((chip8.v[1], chip8.v[0xF]), (0b10, 1));
+
+    let chip8 = Machine::new()
+        .quirks(Quirks::COSMAC_VIP)
+        .v(1, 0b101)
+        .v(2, 0b1000)
+        .exec(0x8126);
+    assert_eq!((chip8.v[1], chip8.v[0xF]), (0b100, 0));
+}
+
+#[test]
+fn op_8xy6_vf_as_operand() {
+    let chip8 = Machine::new().v(0xF, 0b11).exec(0x8F06);
+    assert_eq!(chip8.v[0xF], 1);
+}
+
+#[test]
+fn op_8xy7_subtracts_reversed() {
+    let chip8 = Machine::new().v(1, 3).v(2, 5).exec(0x8127);
+    assert_eq!((chip8.v[1], chip8.v[0xF]), (2, 1));
+
+    let chip8 = Machine::new().v(1, 5).v(2, 3).exec(0x8127);
+    assert_eq!((chip8.v[1], chip8.v[0xF]), (0xFE, 0));
+
+    let chip8 = Machine::new().v(1, 100).v(2, 200).exec(0x8127);
+    assert_eq!((chip8.v[1], chip8.v[0xF]), (100, 1));
+}
+
+#[test]
+fn op_8xy7_vf_as_operand() {
+    let chip8 = Machine::new().v(0xF, 5).v(1, 3).exec(0x8F17);
+    assert_eq!(chip8.v[0xF], 0);
+}
+
+#[test]
+fn op_8xye_shifts_left() {
+    let chip8 = Machine::new().v(1, 0x81).exec(0x812E);
+    assert_eq!((chip8.v[1], chip8.v[0xF]), (0x02, 1));
+
+    let chip8 = Machine::new().v(1, 0x7F).exec(0x812E);
+    assert_eq!((chip8.v[1], chip8.v[0xF]), (0xFE, 0));
+
+    let chip8 = Machine::new()
+        .quirks(Quirks::COSMAC_VIP)
+        .v(1, 0x01)
+        .v(2, 0xC0)
+        .exec(0x812E);
+    assert_eq!((chip8.v[1], chip8.v[0xF]), (0x80, 1));
+}
+
+#[test]
+fn op_8xye_vf_as_operand() {
+    let chip8 = Machine::new().v(0xF, 0x40).exec(0x8F0E);
+    assert_eq!(chip8.v[0xF], 0);
+}
+
+#[test]
+fn op_9xy0_skips_if_registers_differ() {
+    assert_eq!(Machine::new().v(1, 7).v(2, 8).exec(0x9120).pc, SKIPPED);
+    assert_eq!(Machine::new().v(1, 7).v(2, 7).exec(0x9120).pc, NEXT);
+}
+
+#[test]
+fn op_annn_loads_i() {
+    let chip8 = Machine::new().exec(0xA123);
+    assert_eq!(chip8.i, 0x123);
+}
+
+#[test]
+fn op_bnnn_jumps_with_offset() {
+    let chip8 = Machine::new()
+        .quirks(Quirks::COSMAC_VIP)
+        .v(0, 4)
+        .v(3, 8)
+        .exec(0xB300);
+    assert_eq!(chip8.pc, 0x304);
+
+    let chip8 = Machine::new()
+        .quirks(Quirks::SCHIP)
+        .v(0, 4)
+        .v(3, 8)
+        .exec(0xB300);
+    assert_eq!(chip8.pc, 0x308);
+
+    // Plain CHIP-8 jump tables work without picking quirks.
+    let chip8 = Machine::new().v(0, 4).v(3, 8).exec(0xB300);
+    assert_eq!(chip8.pc, 0x304);
+}
+
+#[test]
+fn op_cxnn_masks_random_byte() {
+    for _ in 0..32 {
+        assert_eq!(Machine::new().v(1, 0xFF).exec(0xC100).v[1], 0);
+        assert_eq!(Machine::new().exec(0xC10F).v[1] & 0xF0, 0);
+    }
+}
+
+#[test]
+fn op_cxnn_follows_seed() {
+    let draws = |seed| {
+        let mut chip8 = Machine::new().seed(seed).exec(0xC1FF);
+        let first = chip8.v[1];
+        chip8.decode_op(Chip8::split_op(0xC1, 0xFF)).unwrap();
+        (first, chip8.v[1])
+    };
+    assert_eq!(draws(1), draws(1));
+    assert_ne!(draws(1), draws(2));
+
+    // The generator's position is part of the saved state.
+    let mut chip8 = Machine::new().seed(7).exec(0xC1FF);
+    let mut restored = Chip8::new();
+    restored.load_state(&chip8.save_state()).unwrap();
+    assert_eq!(restored.seed(), 7);
+    for chip8 in [&mut chip8, &mut restored] {
+        chip8.decode_op(Chip8::split_op(0xC1, 0xFF)).unwrap();
+    }
+    assert_eq!(chip8.v[1], restored.v[1]);
+}
+
+#[test]
+fn op_dxyn_draws_and_collides() {
+    let chip8 = Machine::new()
+        .i(0x300)
+        .ram(0x300, &[0xC0, 0x80])
+        .v(1, 10)
+        .v(2, 5)
+        .exec(0xD122);
+    assert_eq!(lit_pixels(&chip8), 3);
+    assert_eq!(pixel(&chip8, 10, 5), 1);
+    assert_eq!(pixel(&chip8, 11, 5), 1);
+    assert_eq!(pixel(&chip8, 10, 6), 1);
+    assert_eq!(chip8.v[0xF], 0);
+
+    let chip8 = Machine { chip8 }.exec(0xD122);
+    assert_eq!(lit_pixels(&chip8), 0);
+    assert_eq!(chip8.v[0xF], 1);
+}
+
+#[test]
+fn op_dxyn_wraps_origin() {
+    let chip8 = Machine::new()
+        .i(0x300)
+        .ram(0x300, &[0x80])
+        .v(1, 66)
+        .v(2, 33)
+        .exec(0xD121);
+    assert_eq!(pixel(&chip8, 2, 1), 1);
+}
+
+#[test]
+fn op_dxyn_clips_or_wraps_at_edges() {
+    let machine = |quirks| {
+        Machine::new()
+            .quirks(quirks)
+            .i(0x300)
+            .ram(0x300, &[0xFF, 0xFF])
+            .v(1, 60)
+            .v(2, 31)
+    };
+    let chip8 = machine(Quirks::SCHIP).exec(0xD122);
+    assert_eq!(lit_pixels(&chip8), 4);
+
+    let chip8 = machine(Quirks::XO_CHIP).exec(0xD122);
+    assert_eq!(lit_pixels(&chip8), 16);
+    assert_eq!(machine(Quirks::default()).exec(0xD122).vram, chip8.vram);
+    assert_eq!(pixel(&chip8, 3, 0), 1);
+}
+
+#[test]
+fn op_dxyn_out_of_bounds() {
+    let err = Machine::new().i(0xFFE).try_exec(0xD125).err();
+    assert_eq!(
+        err,
+        Some(Chip8Error::MemoryOutOfBounds {
+            pc: PC,
+            opcode: 0xD125,
+            addr: 0x1002
+        })
+    );
+}
+
+#[test]
+fn op_fn01_selects_planes_to_draw_on() {
+    let chip8 = Machine::xo_chip()
+        .i(0x300)
+        .ram(0x300, &[0x80, 0x40])
+        .exec(0xF201);
+    let chip8 = Machine { chip8 }.exec(0xD011);
+    assert_eq!(pixel(&chip8, 0, 0), 0x2);
+    assert_eq!(lit_pixels(&chip8), 1);
+
+    // The second plane's sprite follows the first's.
+    let chip8 = Machine { chip8 }.exec(0xF301);
+    let chip8 = Machine { chip8 }.exec(0xD011);
+    assert_eq!(pixel(&chip8, 0, 0), 0x3);
+    assert_eq!(pixel(&chip8, 1, 0), 0x2);
+
+    let chip8 = Machine { chip8 }.exec(0xF001);
+    let chip8 = Machine { chip8 }.exec(0xD011);
+    assert_eq!(chip8.v[0xF], 0);
+    assert_eq!(lit_pixels(&chip8), 2);
+}
+
+#[test]
+fn op_fn01_selects_planes_to_scroll_and_clear() {
+    let mut chip8 = Machine::xo_chip().exec(0xF201);
+    chip8.vram[0] = 0x3;
+    let chip8 = Machine { chip8 }.exec(0x00C1);
+    assert_eq!(pixel(&chip8, 0, 0), 0x1);
+    assert_eq!(pixel(&chip8, 0, 1), 0x2);
+
+    let chip8 = Machine { chip8 }.exec(0x00D1);
+    assert_eq!(pixel(&chip8, 0, 0), 0x3);
+
+    let chip8 = Machine { chip8 }.exec(0x00FB);
+    assert_eq!(pixel(&chip8, 0, 0), 0x1);
+    assert_eq!(pixel(&chip8, 4, 0), 0x2);
+
+    let chip8 = Machine { chip8 }.exec(0x00E0);
+    assert_eq!(pixel(&chip8, 0, 0), 0x1);
+    assert_eq!(lit_pixels(&chip8), 1);
+}
+
+#[test]
+fn op_dxy0_draws_16x16_sprite() {
+    let mut sprite = [0xFF; 32];
+    sprite[31] = 0xFE;
+    let chip8 = Machine::new()
+        .hires()
+        .i(0x300)
+        .ram(0x300, &sprite)
+        .v(1, 100)
+        .v(2, 40)
+        .exec(0xD120);
+    assert_eq!(lit_pixels(&chip8), 255);
+    assert_eq!(pixel(&chip8, 100, 40), 1);
+    assert_eq!(pixel(&chip8, 114, 55), 1);
+    assert_eq!(pixel(&chip8, 115, 55), 0);
+    assert_eq!(pixel(&chip8, 116, 40), 0);
+}
+
+#[test]
+fn op_dxyn_counts_colliding_rows_in_hires() {
+    let machine = |hires| {
+        let machine = Machine::new().quirks(Quirks::SCHIP);
+        let machine = if hires { machine.hires() } else { machine };
+        machine
+            .i(0x300)
+            .ram(0x300, &[0x80, 0x80, 0x80])
+            .pixel(0, 0)
+            .pixel(0, 2)
+    };
+    assert_eq!(machine(true).exec(0xD113).v[0xF], 2);
+    assert_eq!(machine(false).exec(0xD113).v[0xF], 1);
+
+    // Rows clipped off the bottom count as well.
+    let chip8 = machine(true).v(2, 62).exec(0xD123);
+    assert_eq!(chip8.v[0xF], 1);
+    assert_eq!(lit_pixels(&chip8), 4);
+}
+
+#[test]
+fn op_ex9e_skips_if_key_down() {
+    assert_eq!(Machine::new().v(1, 0xA).key(0xA).exec(0xE19E).pc, SKIPPED);
+    assert_eq!(Machine::new().v(1, 0xA).key(0xB).exec(0xE19E).pc, NEXT);
+}
+
+#[test]
+fn op_exa1_skips_if_key_up() {
+    assert_eq!(Machine::new().v(1, 0xA).key(0xB).exec(0xE1A1).pc, SKIPPED);
+    assert_eq!(Machine::new().v(1, 0xA).key(0xA).exec(0xE1A1).pc, NEXT);
+}
+
+#[test]
+fn op_fx07_reads_delay_timer() {
+    let chip8 = Machine::new().delay_timer(42).exec(0xF107);
+    assert_eq!(chip8.v[1], 42);
+}
+
+#[test]
+fn op_fx0a_waits_for_release() {
+    let mut chip8 = Machine::new().key(0x3).exec(0xF50A);
+    assert!(chip8.is_waiting_for_key());
+    assert!(chip8.wait_for_key());
+
+    chip8.set_key(0x3, false);
+    assert!(!chip8.wait_for_key());
+    assert!(!chip8.is_waiting_for_key());
+    assert_eq!(chip8.v[5], 0x3);
+}
+
+#[test]
+fn op_fx0a_ignores_earlier_release() {
+    let mut chip8 = Machine::new().key(0x3).chip8;
+    chip8.set_key(0x3, false);
+    let mut chip8 = Machine { chip8 }.exec(0xF50A);
+    assert!(chip8.wait_for_key());
+}
+
+#[test]
+fn op_f000_loads_long_i() {
+    let chip8 = Machine::xo_chip()
+        .ram(NEXT as usize, &[0xFE, 0xDC])
+        .exec(0xF000);
+    assert_eq!(chip8.i, 0xFEDC);
+    assert_eq!(chip8.pc, SKIPPED);
+
+    let err = Machine::new().try_exec(0xF000).err();
+    assert_eq!(
+        err,
+        Some(Chip8Error::InvalidOpcode {
+            pc: PC,
+            opcode: 0xF000
+        })
+    );
+}
+
+#[test]
+fn skips_over_long_load() {
+    let machine = || Machine::xo_chip().ram(NEXT as usize, &[0xF0, 0x00, 0x12, 0x34]);
+    assert_eq!(machine().v(1, 5).exec(0x3105).pc, SKIPPED + 2);
+    assert_eq!(machine().v(1, 5).exec(0x4105).pc, NEXT);
+    assert_eq!(machine().key(0x0).exec(0xE09E).pc, SKIPPED + 2);
+
+    // Without XO-CHIP the F000 is just another two bytes.
+    let chip8 = Machine::new()
+        .ram(NEXT as usize, &[0xF0, 0x00])
+        .v(1, 5)
+        .exec(0x3105);
+    assert_eq!(chip8.pc, SKIPPED);
+}
+
+#[test]
+fn op_f002_fx3a_set_audio_pattern_and_pitch() {
+    let pattern: Vec<u8> = (0..16).collect();
+    let chip8 = Machine::xo_chip()
+        .i(0x300)
+        .ram(0x300, &pattern)
+        .exec(0xF002);
+    assert_eq!(chip8.audio_pattern(), &pattern[..]);
+    assert_eq!(chip8.audio_rate(), 4000.0);
+
+    assert_eq!(
+        Machine::xo_chip().v(1, 112).exec(0xF13A).audio_rate(),
+        8000.0
+    );
+    assert_eq!(
+        Machine::xo_chip().v(1, 16).exec(0xF13A).audio_rate(),
+        2000.0
+    );
+}
+
+#[test]
+fn op_fx15_fx18_set_timers() {
+    let chip8 = Machine::new().v(1, 30).exec(0xF115);
+    assert_eq!(chip8.delay_timer, 30);
+    let chip8 = Machine::new().v(1, 20).exec(0xF118);
+    assert_eq!(chip8.sound_timer, 20);
+}
+
+#[test]
+fn op_fx1e_adds_to_i() {
+    let chip8 = Machine::new().i(0x100).v(1, 0x20).exec(0xF11E);
+    assert_eq!(chip8.i, 0x120);
+}
+
+#[test]
+fn op_fx1e_overflow() {
+    let chip8 = Machine::new().i(0xFFF).v(1, 1).v(0xF, 0).exec(0xF11E);
+    assert_eq!(chip8.i, 0x1000);
+    assert_eq!(chip8.v[0xF], 0);
+
+    let chip8 = Machine::new().i(0xFFFF).v(1, 2).exec(0xF11E);
+    assert_eq!(chip8.i, 0x0001);
+}
+
+#[test]
+fn op_fx29_points_at_font() {
+    let chip8 = Machine::new().v(1, 0xA).exec(0xF129);
+    assert_eq!(chip8.i, 50);
+    assert_eq!(&chip8.ram[50..55], &Chip8::FONT_SET[50..55]);
+
+    let chip8 = Machine::new().v(1, 0x1A).exec(0xF129);
+    assert_eq!(chip8.i, 50);
+}
+
+#[test]
+fn op_fx30_points_at_big_font() {
+    let chip8 = Machine::new().v(1, 0xA).exec(0xF130);
+    assert_eq!(chip8.i, 180);
+    assert_eq!(&chip8.ram[180..190], &Chip8::BIG_FONT_SET[100..110]);
+
+    let chip8 = Machine::new().v(1, 0x1F).exec(0xF130);
+    assert_eq!(chip8.i, 230);
+}
+
+#[test]
+fn op_fx33_stores_bcd() {
+    let chip8 = Machine::new().i(0x300).v(1, 234).exec(0xF133);
+    assert_eq!(&chip8.ram[0x300..0x303], &[2, 3, 4]);
+    assert_eq!(chip8.i, 0x300);
+
+    let chip8 = Machine::new().i(0x300).v(1, 7).exec(0xF133);
+    assert_eq!(&chip8.ram[0x300..0x303], &[0, 0, 7]);
+}
+
+#[test]
+fn op_fx33_top_of_ram() {
+    let chip8 = Machine::new().i(0xFFD).v(1, 255).exec(0xF133);
+    assert_eq!(&chip8.ram[0xFFD..], &[2, 5, 5]);
+
+    let err = Machine::new().i(0xFFE).v(1, 255).try_exec(0xF133).err();
+    assert_eq!(
+        err,
+        Some(Chip8Error::MemoryOutOfBounds {
+            pc: PC,
+            opcode: 0xF133,
+            addr: 0x1000
+        })
+    );
+}
+
+#[test]
+fn op_fx55_stores_registers() {
+    let machine = |quirks| {
+        Machine::new()
+            .quirks(quirks)
+            .i(0x300)
+            .v(0, 1)
+            .v(1, 2)
+            .v(2, 3)
+            .v(3, 4)
+    };
+    let chip8 = machine(Quirks::SCHIP).exec(0xF255);
+    assert_eq!(&chip8.ram[0x300..0x304], &[1, 2, 3, 0]);
+    assert_eq!(chip8.i, 0x300);
+
+    assert_eq!(machine(Quirks::CHIP_48).exec(0xF255).i, 0x302);
+    assert_eq!(machine(Quirks::COSMAC_VIP).exec(0xF255).i, 0x303);
+}
+
+#[test]
+fn op_fx55_top_of_ram() {
+    let err = Machine::new().i(0xFFE).try_exec(0xF255).err();
+    assert_eq!(
+        err,
+        Some(Chip8Error::MemoryOutOfBounds {
+            pc: PC,
+            opcode: 0xF255,
+            addr: 0x1000
+        })
+    );
+}
+
+#[test]
+fn op_fx65_loads_registers() {
+    let machine = |quirks| {
+        Machine::new()
+            .quirks(quirks)
+            .i(0x300)
+            .ram(0x300, &[1, 2, 3, 4])
+            .v(3, 9)
+    };
+    let chip8 = machine(Quirks::SCHIP).exec(0xF265);
+    assert_eq!(&chip8.v[0..4], &[1, 2, 3, 9]);
+    assert_eq!(chip8.i, 0x300);
+
+    assert_eq!(machine(Quirks::CHIP_48).exec(0xF265).i, 0x302);
+    assert_eq!(machine(Quirks::COSMAC_VIP).exec(0xF265).i, 0x303);
+}
+
+#[test]
+fn xo_chip_i_reaches_all_of_memory() {
+    let chip8 = Machine::xo_chip().i(0xFFFE).v(0, 1).v(1, 2).exec(0xF155);
+    assert_eq!(&chip8.ram[0xFFFE..], &[1, 2]);
+
+    let chip8 = Machine::xo_chip()
+        .i(0xFFFE)
+        .ram(0xFFFE, &[3, 4])
+        .exec(0xF165);
+    assert_eq!(&chip8.v[0..2], &[3, 4]);
+
+    let err = Machine::xo_chip().i(0xFFFE).try_exec(0xF255).err();
+    assert_eq!(
+        err,
+        Some(Chip8Error::MemoryOutOfBounds {
+            pc: PC,
+            opcode: 0xF255,
+            addr: 0x10000
+        })
+    );
+}
+
+#[test]
+fn op_fx75_fx85_round_trip_flags() {
+    let chip8 = Machine::new().v(0, 1).v(1, 2).v(2, 3).v(3, 4).exec(0xF275);
+    let chip8 = Machine { chip8 }.v(0, 0).v(1, 0).v(2, 0).exec(0xF385);
+    assert_eq!(&chip8.v[0..4], &[1, 2, 3, 0]);
+
+    let chip8 = Machine { chip8 }.v(0, 9).v(1, 9).exec(0xF085);
+    assert_eq!(&chip8.v[0..2], &[1, 9]);
+}
+
+#[test]
+fn fetch_wraps_at_end_of_xo_memory() {
+    let mut chip8 = Chip8::new_xo_chip();
+    chip8.ram[0xFFFE..].copy_from_slice(&[0x60, 0x05]);
+    chip8.pc = 0xFFFE;
+    chip8.step().unwrap();
+
+    assert_eq!(chip8.pc, 0);
+    assert_eq!(chip8.v[0], 5);
+}
+
+#[test]
+fn jump_to_self_is_spinning() {
+    let mut chip8 = Chip8::new_xo_chip();
+    chip8.ram[0x234..0x236].copy_from_slice(&[0x12, 0x34]);
+    chip8.pc = 0x234;
+    assert!(chip8.is_spinning());
+
+    // The same opcode at 0x1234 jumps down to 0x234.
+    chip8.ram[0x1234..0x1236].copy_from_slice(&[0x12, 0x34]);
+    chip8.pc = 0x1234;
+    assert!(!chip8.is_spinning());
+}
+
+#[test]
+fn reset_keeps_mode_and_quirks() {
+    let mut chip8 = Chip8::new_xo_chip();
+    chip8.set_quirks(Quirks::SCHIP);
+    chip8.load_rom(&[0x60, 0x05]).unwrap();
+    chip8.step().unwrap();
+    chip8.reset(&[0x61, 0x07]).unwrap();
+
+    assert_eq!(chip8.pc, PC);
+    assert_eq!(chip8.v[0], 0);
+    assert!(chip8.is_xo_chip());
+    assert_eq!(chip8.quirks(), Quirks::SCHIP);
+    assert_eq!(&chip8.ram[PC as usize..][..2], &[0x61, 0x07]);
+}
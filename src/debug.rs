@@ -0,0 +1,245 @@
+use rustichip8::disasm::{self, Syntax};
+use rustichip8::{Chip8, Chip8Error, Debugger, Rewind, Stop, Watchpoint};
+use std::fmt::Write;
+use termion::event::Key;
+
+/// Pauses a running program and opens the command prompt.
+pub const BREAK_KEY: Key = Key::F(10);
+
+const HELP: &str = "s step | n next | rs step back | rf [N] frames back | c continue | \
+                    b/db ADDR break | w/dw i|vX|ADDR watch | m ADDR|i memory | q quit";
+// Instructions shown before and after PC in the disassembly.
+const DISASM_CONTEXT: u16 = 4;
+const MEMORY_ROWS: usize = 4;
+
+/// The `--debug` console: a prompt shown under the screen that drives a
+/// `Debugger`. Keys go to the prompt while paused and to the keypad while
+/// running. A fault pauses the console rather than ending the program, so
+/// the steps leading up to it can be rewound.
+pub struct Console {
+    debugger: Debugger,
+    paused: bool,
+    input: String,
+    last_command: String,
+    // Start of the memory view, or None to follow I.
+    memory_view: Option<u16>,
+    status: String,
+    quit: bool,
+}
+
+impl Console {
+    /// A console that starts paused before the first instruction.
+    pub fn new() -> Self {
+        Console {
+            debugger: Debugger::new(),
+            paused: true,
+            input: String::new(),
+            last_command: String::new(),
+            memory_view: None,
+            status: HELP.to_string(),
+            quit: false,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// True once the quit command has been entered.
+    pub fn wants_quit(&self) -> bool {
+        self.quit
+    }
+
+    pub fn pause(&mut self, status: String) {
+        self.paused = true;
+        self.status = status;
+    }
+
+    /// Edits the prompt, running the command on Enter. An empty command
+    /// repeats the previous one. Commands that execute instructions take a
+    /// snapshot in `rewind` first.
+    pub fn handle_key(&mut self, key: Key, chip8: &mut Chip8, rewind: &mut Rewind) {
+        match key {
+            Key::Char('\n') => {
+                let mut command = std::mem::take(&mut self.input);
+                if command.trim().is_empty() {
+                    command = self.last_command.clone();
+                }
+                self.execute(&command, chip8, rewind);
+                self.last_command = command;
+            }
+            Key::Char(c) => self.input.push(c),
+            Key::Backspace => {
+                self.input.pop();
+            }
+            _ => {}
+        }
+    }
+
+    /// Runs a frame unless paused, taking a snapshot in `rewind` first.
+    /// Hitting a breakpoint or watchpoint pauses the console, and so does a
+    /// fault.
+    pub fn run_frame(&mut self, chip8: &mut Chip8, rewind: &mut Rewind, instructions: u32) {
+        if self.paused && !self.debugger.is_stepping_over() {
+            return;
+        }
+        rewind.snapshot(chip8);
+        let result = self.debugger.run_frame(chip8, instructions);
+        if !matches!(result, Ok(None)) {
+            self.pause(stop_status(result));
+        }
+    }
+
+    fn execute(&mut self, command: &str, chip8: &mut Chip8, rewind: &mut Rewind) {
+        let mut words = command.split_whitespace();
+        let name = words.next().unwrap_or("");
+        let arg = words.next();
+        self.status = match (name, arg) {
+            ("s", None) | ("step", None) => {
+                rewind.snapshot(chip8);
+                stop_status(self.debugger.step(chip8))
+            }
+            ("n", None) | ("next", None) => {
+                rewind.snapshot(chip8);
+                match self.debugger.step_over(chip8) {
+                    Ok(None) if self.debugger.is_stepping_over() => {
+                        "Stepping over call".to_string()
+                    }
+                    result => stop_status(result),
+                }
+            }
+            ("rs", None) => match rewind.back_step(chip8) {
+                Ok(true) => format!("Stepped back to {:03X}", chip8.pc()),
+                Ok(false) => "Nothing to step back to".to_string(),
+                Err(err) => format!("Fault while replaying: {}", err),
+            },
+            ("rf", frames) => match frames.map_or(Some(1), |n| n.parse::<usize>().ok()) {
+                Some(frames) => {
+                    let rewound = (0..frames).take_while(|_| rewind.back_frame(chip8)).count();
+                    format!("Rewound {} frames, {} left", rewound, rewind.len())
+                }
+                None => HELP.to_string(),
+            },
+            ("c", None) | ("continue", None) => {
+                self.paused = false;
+                format!("Running, {:?} to break", BREAK_KEY)
+            }
+            ("b", Some(addr)) => match parse_addr(addr) {
+                Some(addr) => {
+                    self.debugger.add_breakpoint(addr);
+                    format!("Breakpoint at {:03X}", addr)
+                }
+                None => format!("Bad address {}", addr),
+            },
+            ("db", Some(addr)) => match parse_addr(addr) {
+                Some(addr) if self.debugger.remove_breakpoint(addr) => {
+                    format!("Removed breakpoint at {:03X}", addr)
+                }
+                _ => format!("No breakpoint at {}", addr),
+            },
+            ("w", Some(watch)) => match parse_watchpoint(watch) {
+                Some(watch) => {
+                    self.debugger.add_watchpoint(watch);
+                    format!("Watching {}", watch)
+                }
+                None => format!("Bad watchpoint {}", watch),
+            },
+            ("dw", Some(watch)) => match parse_watchpoint(watch) {
+                Some(watch) if self.debugger.remove_watchpoint(watch) => {
+                    format!("Stopped watching {}", watch)
+                }
+                _ => format!("Not watching {}", watch),
+            },
+            ("m", Some("i")) | ("m", Some("I")) => {
+                self.memory_view = None;
+                "Memory view follows I".to_string()
+            }
+            ("m", Some(addr)) => match parse_addr(addr) {
+                Some(addr) => {
+                    self.memory_view = Some(addr);
+                    format!("Memory view at {:03X}", addr)
+                }
+                None => format!("Bad address {}", addr),
+            },
+            ("q", None) | ("quit", None) => {
+                self.quit = true;
+                String::new()
+            }
+            _ => HELP.to_string(),
+        }
+    }
+
+    /// Appends the registers, disassembly, memory and prompt to `out`.
+    pub fn render(&self, chip8: &Chip8, out: &mut String) {
+        out.push_str(&chip8.to_string().replace('\n', "\r\n"));
+
+        let pc = chip8.pc();
+        let ram = chip8.ram();
+        let start = pc.saturating_sub(DISASM_CONTEXT * 2);
+        for addr in (start..=pc.saturating_add(DISASM_CONTEXT * 2)).step_by(2) {
+            let a = addr as usize;
+            if a + 1 >= ram.len() {
+                break;
+            }
+            let opcode = u16::from(ram[a]) << 8 | u16::from(ram[a + 1]);
+            let marker = if addr == pc { '>' } else { ' ' };
+            let bp = if self.debugger.breakpoints().any(|&b| b == addr) {
+                '*'
+            } else {
+                ' '
+            };
+            let text =
+                disasm::mnemonic(opcode, Syntax::Cowgod).unwrap_or_else(|| "???".to_string());
+            let _ = write!(
+                out,
+                "{}{} {:03X}  {:04X}  {}\r\n",
+                marker, bp, addr, opcode, text
+            );
+        }
+
+        let base = (self.memory_view.unwrap_or_else(|| chip8.i()) & !0xF) as usize;
+        let rows = ram[base.min(ram.len())..].chunks(16).take(MEMORY_ROWS);
+        for (n, row) in rows.enumerate() {
+            let _ = write!(out, "{:03X}:", base + n * 16);
+            for byte in row {
+                let _ = write!(out, " {:02X}", byte);
+            }
+            out.push_str("\r\n");
+        }
+
+        let watches: Vec<String> = self.debugger.watchpoints().map(|w| w.to_string()).collect();
+        if !watches.is_empty() {
+            let _ = write!(out, "Watching: {}\r\n", watches.join(" "));
+        }
+        let _ = write!(out, "{}\r\n", self.status);
+        if self.paused {
+            let _ = write!(out, "(debug) {}", self.input);
+        }
+    }
+}
+
+fn stop_status(result: Result<Option<Stop>, Chip8Error>) -> String {
+    match result {
+        Ok(Some(stop)) => format!("Stopped: {}", stop),
+        Ok(None) => String::new(),
+        Err(err) => format!("Fault: {}", err),
+    }
+}
+
+fn parse_addr(text: &str) -> Option<u16> {
+    let digits = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_prefix("0X"))
+        .unwrap_or(text);
+    u16::from_str_radix(digits, 16).ok()
+}
+
+fn parse_watchpoint(text: &str) -> Option<Watchpoint> {
+    match text {
+        "i" | "I" => Some(Watchpoint::I),
+        _ if text.len() == 2 && text.starts_with(['v', 'V']) => {
+            u8::from_str_radix(&text[1..], 16).ok().map(Watchpoint::V)
+        }
+        _ => parse_addr(text).map(Watchpoint::Ram),
+    }
+}
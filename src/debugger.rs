@@ -0,0 +1,238 @@
+use crate::chip8::Chip8;
+use crate::error::Chip8Error;
+use std::collections::BTreeSet;
+use std::fmt::{self, Display, Formatter};
+
+/// A location whose value is watched for changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Watchpoint {
+    I,
+    V(u8),
+    Ram(u16),
+}
+
+impl Watchpoint {
+    fn read(self, chip8: &Chip8) -> u16 {
+        match self {
+            Watchpoint::I => chip8.i(),
+            Watchpoint::V(x) => u16::from(chip8.v()[(x & 0x0F) as usize]),
+            Watchpoint::Ram(addr) => chip8.ram().get(addr as usize).map_or(0, |&b| u16::from(b)),
+        }
+    }
+}
+
+impl Display for Watchpoint {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Watchpoint::I => write!(f, "I"),
+            Watchpoint::V(x) => write!(f, "V{:X}", x),
+            Watchpoint::Ram(addr) => write!(f, "[{:03X}]", addr),
+        }
+    }
+}
+
+/// Why execution stopped before the end of a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    /// The program counter reached a breakpoint.
+    Breakpoint(u16),
+    /// A watched value changed from `old` to `new`.
+    Watchpoint {
+        watch: Watchpoint,
+        old: u16,
+        new: u16,
+    },
+    /// A step over a subroutine call returned.
+    StepOverDone,
+    /// The program executed 00FD.
+    Halted,
+}
+
+impl Display for Stop {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Stop::Breakpoint(addr) => write!(f, "breakpoint at {:03X}", addr),
+            Stop::Watchpoint { watch, old, new } => {
+                write!(f, "{} changed from {:02X} to {:02X}", watch, old, new)
+            }
+            Stop::StepOverDone => write!(f, "returned from call"),
+            Stop::Halted => write!(f, "program exited"),
+        }
+    }
+}
+
+/// Breakpoints and watchpoints layered over `Chip8::step`.
+#[derive(Default)]
+pub struct Debugger {
+    breakpoints: BTreeSet<u16>,
+    watchpoints: BTreeSet<Watchpoint>,
+    // Where a step over should stop: the return address and the stack depth
+    // it must be reached at, so recursive calls don't stop early.
+    return_to: Option<(u16, usize)>,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Debugger::default()
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = &u16> {
+        self.breakpoints.iter()
+    }
+
+    pub fn add_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.insert(addr);
+    }
+
+    /// Returns false if there was no breakpoint at `addr`.
+    pub fn remove_breakpoint(&mut self, addr: u16) -> bool {
+        self.breakpoints.remove(&addr)
+    }
+
+    pub fn watchpoints(&self) -> impl Iterator<Item = &Watchpoint> {
+        self.watchpoints.iter()
+    }
+
+    pub fn add_watchpoint(&mut self, watch: Watchpoint) {
+        self.watchpoints.insert(watch);
+    }
+
+    /// Returns false if `watch` wasn't being watched.
+    pub fn remove_watchpoint(&mut self, watch: Watchpoint) -> bool {
+        self.watchpoints.remove(&watch)
+    }
+
+    /// Executes a single instruction, reporting a watchpoint if one fired.
+    pub fn step(&mut self, chip8: &mut Chip8) -> Result<Option<Stop>, Chip8Error> {
+        let before: Vec<u16> = self.watchpoints.iter().map(|w| w.read(chip8)).collect();
+        chip8.step()?;
+        if chip8.halted() {
+            return Ok(Some(Stop::Halted));
+        }
+        for (&watch, &old) in self.watchpoints.iter().zip(before.iter()) {
+            let new = watch.read(chip8);
+            if new != old {
+                return Ok(Some(Stop::Watchpoint { watch, old, new }));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Steps over the instruction at PC. A 2NNN call isn't followed; instead
+    /// the next frames run until it returns, which is reported as
+    /// `Stop::StepOverDone`. Returns `None` in that case, or the result of a
+    /// plain `step` for any other instruction.
+    pub fn step_over(&mut self, chip8: &mut Chip8) -> Result<Option<Stop>, Chip8Error> {
+        let pc = chip8.pc() as usize;
+        if chip8.ram().get(pc).is_some_and(|b| b >> 4 == 0x2) {
+            self.return_to = Some((chip8.pc().wrapping_add(2), chip8.stack().len()));
+            return Ok(None);
+        }
+        self.step(chip8)
+    }
+
+    /// True while a step over is waiting for its call to return.
+    pub fn is_stepping_over(&self) -> bool {
+        self.return_to.is_some()
+    }
+
+    /// Runs one frame like `Chip8::run_frame`, but stops as soon as a
+    /// breakpoint is reached, a watchpoint fires or a step over completes.
+    /// The instruction at the starting PC always executes, so continuing
+    /// from a breakpoint makes progress. Timers are only ticked if the whole
+    /// frame ran.
+    pub fn run_frame(
+        &mut self,
+        chip8: &mut Chip8,
+        instructions: u32,
+    ) -> Result<Option<Stop>, Chip8Error> {
+        for _ in 0..instructions {
+            if let Some(stop) = self.step(chip8)? {
+                self.return_to = None;
+                return Ok(Some(stop));
+            }
+            if let Some((addr, depth)) = self.return_to {
+                if chip8.pc() == addr && chip8.stack().len() == depth {
+                    self.return_to = None;
+                    return Ok(Some(Stop::StepOverDone));
+                }
+            }
+            if self.breakpoints.contains(&chip8.pc()) {
+                self.return_to = None;
+                return Ok(Some(Stop::Breakpoint(chip8.pc())));
+            }
+        }
+        chip8.tick_timers();
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn machine(rom: &[u8]) -> Chip8 {
+        let mut chip8 = Chip8::new();
+        chip8.load_rom(rom).unwrap();
+        chip8
+    }
+
+    #[test]
+    fn breakpoint_stops_after_starting_instruction() {
+        // 7001, then jump back to it.
+        let mut chip8 = machine(&[0x70, 0x01, 0x12, 0x00]);
+        let mut debugger = Debugger::new();
+        debugger.add_breakpoint(0x200);
+        for count in 1..=2 {
+            let stop = debugger.run_frame(&mut chip8, 10).unwrap();
+            assert_eq!(stop, Some(Stop::Breakpoint(0x200)));
+            assert_eq!(chip8.v()[0], count);
+        }
+    }
+
+    #[test]
+    fn watchpoints_report_old_and_new_values() {
+        // 6105, 7102, A123
+        let mut chip8 = machine(&[0x61, 0x05, 0x71, 0x02, 0xA1, 0x23]);
+        let mut debugger = Debugger::new();
+        debugger.add_watchpoint(Watchpoint::V(1));
+        debugger.add_watchpoint(Watchpoint::I);
+        let watch = |watch, old, new| Some(Stop::Watchpoint { watch, old, new });
+        assert_eq!(debugger.step(&mut chip8), Ok(watch(Watchpoint::V(1), 0, 5)));
+        assert_eq!(
+            debugger.run_frame(&mut chip8, 10),
+            Ok(watch(Watchpoint::V(1), 5, 7))
+        );
+        assert_eq!(
+            debugger.run_frame(&mut chip8, 10),
+            Ok(watch(Watchpoint::I, 0, 0x123))
+        );
+    }
+
+    #[test]
+    fn step_over_waits_for_the_same_depth() {
+        // A subroutine at 206 that counts V0 down to zero by jumping back
+        // to the call at 202, so every level returns to 204.
+        let rom = [
+            0x60, 0x02, // 200: LD V0, 2
+            0x22, 0x06, // 202: CALL 206
+            0x00, 0xEE, // 204: RET
+            0x30, 0x00, // 206: SE V0, 0
+            0x12, 0x0C, // 208: JP 20C
+            0x00, 0xEE, // 20A: RET
+            0x70, 0xFF, // 20C: ADD V0, FF
+            0x12, 0x02, // 20E: JP 202
+        ];
+        let mut chip8 = machine(&rom);
+        let mut debugger = Debugger::new();
+        debugger.step(&mut chip8).unwrap();
+        assert_eq!(debugger.step_over(&mut chip8), Ok(None));
+        assert!(debugger.is_stepping_over());
+
+        let stop = debugger.run_frame(&mut chip8, 100).unwrap();
+        assert_eq!(stop, Some(Stop::StepOverDone));
+        assert_eq!((chip8.pc(), chip8.stack().len()), (0x204, 0));
+        assert_eq!(chip8.v()[0], 0);
+        assert!(!debugger.is_stepping_over());
+    }
+}
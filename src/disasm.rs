@@ -0,0 +1,442 @@
+//! Instruction mnemonics and a recursive-descent ROM disassembler.
+
+use crate::chip8::Chip8;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+/// Assembly dialect used for mnemonics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+    /// The `LD V0, #05` style from Cowgod's technical reference.
+    Cowgod,
+    /// The `v0 := 0x05` style of the Octo assembler.
+    Octo,
+}
+
+impl Syntax {
+    pub fn from_name(name: &str) -> Option<Syntax> {
+        match name.to_ascii_lowercase().as_str() {
+            "cowgod" => Some(Syntax::Cowgod),
+            "octo" => Some(Syntax::Octo),
+            _ => None,
+        }
+    }
+
+    fn hex(self, value: u16, digits: usize) -> String {
+        match self {
+            Syntax::Cowgod => format!("#{:0width$X}", value, width = digits),
+            Syntax::Octo => format!("0x{:0width$X}", value, width = digits),
+        }
+    }
+}
+
+/// Mnemonic for a single opcode, or `None` if the opcode isn't a CHIP-8,
+/// SUPER-CHIP or XO-CHIP instruction. The operand of F000 NNNN is in the
+/// following word and isn't shown.
+pub fn mnemonic(opcode: u16, syntax: Syntax) -> Option<String> {
+    format_op(opcode, None, syntax, &|addr| syntax.hex(addr, 3))
+}
+
+// Formats an instruction, naming addresses with `name`. `long` is the word
+// following an F000.
+fn format_op(
+    opcode: u16,
+    long: Option<u16>,
+    syntax: Syntax,
+    name: &dyn Fn(u16) -> String,
+) -> Option<String> {
+    let op = Chip8::split_op((opcode >> 8) as u8, opcode as u8);
+    let nnn = opcode & 0x0FFF;
+    let nn = syntax.hex(u16::from(opcode as u8), 2);
+    let text = match syntax {
+        Syntax::Cowgod => match op {
+            (0x0, 0x0, 0xC, n) => format!("SCD {}", n),
+            (0x0, 0x0, 0xD, n) => format!("SCU {}", n),
+            (0x0, 0x0, 0xE, 0x0) => "CLS".to_string(),
+            (0x0, 0x0, 0xE, 0xE) => "RET".to_string(),
+            (0x0, 0x0, 0xF, 0xB) => "SCR".to_string(),
+            (0x0, 0x0, 0xF, 0xC) => "SCL".to_string(),
+            (0x0, 0x0, 0xF, 0xD) => "EXIT".to_string(),
+            (0x0, 0x0, 0xF, 0xE) => "LOW".to_string(),
+            (0x0, 0x0, 0xF, 0xF) => "HIGH".to_string(),
+            (0x0, _, _, _) => format!("SYS {}", syntax.hex(nnn, 3)),
+            (0x1, _, _, _) => format!("JP {}", name(nnn)),
+            (0x2, _, _, _) => format!("CALL {}", name(nnn)),
+            (0x3, x, _, _) => format!("SE V{:X}, {}", x, nn),
+            (0x4, x, _, _) => format!("SNE V{:X}, {}", x, nn),
+            (0x5, x, y, 0x0) => format!("SE V{:X}, V{:X}", x, y),
+            (0x5, x, y, 0x2) => format!("LD [I], V{:X}-V{:X}", x, y),
+            (0x5, x, y, 0x3) => format!("LD V{:X}-V{:X}, [I]", x, y),
+            (0x6, x, _, _) => format!("LD V{:X}, {}", x, nn),
+            (0x7, x, _, _) => format!("ADD V{:X}, {}", x, nn),
+            (0x8, x, y, 0x0) => format!("LD V{:X}, V{:X}", x, y),
+            (0x8, x, y, 0x1) => format!("OR V{:X}, V{:X}", x, y),
+            (0x8, x, y, 0x2) => format!("AND V{:X}, V{:X}", x, y),
+            (0x8, x, y, 0x3) => format!("XOR V{:X}, V{:X}", x, y),
+            (0x8, x, y, 0x4) => format!("ADD V{:X}, V{:X}", x, y),
+            (0x8, x, y, 0x5) => format!("SUB V{:X}, V{:X}", x, y),
+            (0x8, x, y, 0x6) => format!("SHR V{:X}, V{:X}", x, y),
+            (0x8, x, y, 0x7) => format!("SUBN V{:X}, V{:X}", x, y),
+            (0x8, x, y, 0xE) => format!("SHL V{:X}, V{:X}", x, y),
+            (0x9, x, y, 0x0) => format!("SNE V{:X}, V{:X}", x, y),
+            (0xA, _, _, _) => format!("LD I, {}", name(nnn)),
+            (0xB, _, _, _) => format!("JP V0, {}", name(nnn)),
+            (0xC, x, _, _) => format!("RND V{:X}, {}", x, nn),
+            (0xD, x, y, n) => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+            (0xE, x, 0x9, 0xE) => format!("SKP V{:X}", x),
+            (0xE, x, 0xA, 0x1) => format!("SKNP V{:X}", x),
+            (0xF, 0x0, 0x0, 0x0) => match long {
+                Some(addr) => format!("LD I, LONG {}", name(addr)),
+                None => "LD I, LONG".to_string(),
+            },
+            (0xF, n, 0x0, 0x1) => format!("PLANE {}", n),
+            (0xF, 0x0, 0x0, 0x2) => "AUDIO".to_string(),
+            (0xF, x, 0x0, 0x7) => format!("LD V{:X}, DT", x),
+            (0xF, x, 0x0, 0xA) => format!("LD V{:X}, K", x),
+            (0xF, x, 0x1, 0x5) => format!("LD DT, V{:X}", x),
+            (0xF, x, 0x1, 0x8) => format!("LD ST, V{:X}", x),
+            (0xF, x, 0x1, 0xE) => format!("ADD I, V{:X}", x),
+            (0xF, x, 0x2, 0x9) => format!("LD F, V{:X}", x),
+            (0xF, x, 0x3, 0x0) => format!("LD HF, V{:X}", x),
+            (0xF, x, 0x3, 0x3) => format!("LD B, V{:X}", x),
+            (0xF, x, 0x3, 0xA) => format!("PITCH V{:X}", x),
+            (0xF, x, 0x5, 0x5) => format!("LD [I], V{:X}", x),
+            (0xF, x, 0x6, 0x5) => format!("LD V{:X}, [I]", x),
+            (0xF, x, 0x7, 0x5) => format!("LD R, V{:X}", x),
+            (0xF, x, 0x8, 0x5) => format!("LD V{:X}, R", x),
+            _ => return None,
+        },
+        Syntax::Octo => match op {
+            (0x0, 0x0, 0xC, n) => format!("scroll-down {}", n),
+            (0x0, 0x0, 0xD, n) => format!("scroll-up {}", n),
+            (0x0, 0x0, 0xE, 0x0) => "clear".to_string(),
+            (0x0, 0x0, 0xE, 0xE) => "return".to_string(),
+            (0x0, 0x0, 0xF, 0xB) => "scroll-right".to_string(),
+            (0x0, 0x0, 0xF, 0xC) => "scroll-left".to_string(),
+            (0x0, 0x0, 0xF, 0xD) => "exit".to_string(),
+            (0x0, 0x0, 0xF, 0xE) => "lores".to_string(),
+            (0x0, 0x0, 0xF, 0xF) => "hires".to_string(),
+            (0x0, _, _, _) => format!("native {}", syntax.hex(nnn, 3)),
+            (0x1, _, _, _) => format!("jump {}", name(nnn)),
+            (0x2, _, _, _) => format!(":call {}", name(nnn)),
+            (0x3, x, _, _) => format!("if v{:x} != {} then", x, nn),
+            (0x4, x, _, _) => format!("if v{:x} == {} then", x, nn),
+            (0x5, x, y, 0x0) => format!("if v{:x} != v{:x} then", x, y),
+            (0x5, x, y, 0x2) => format!("save v{:x} - v{:x}", x, y),
+            (0x5, x, y, 0x3) => format!("load v{:x} - v{:x}", x, y),
+            (0x6, x, _, _) => format!("v{:x} := {}", x, nn),
+            (0x7, x, _, _) => format!("v{:x} += {}", x, nn),
+            (0x8, x, y, 0x0) => format!("v{:x} := v{:x}", x, y),
+            (0x8, x, y, 0x1) => format!("v{:x} |= v{:x}", x, y),
+            (0x8, x, y, 0x2) => format!("v{:x} &= v{:x}", x, y),
+            (0x8, x, y, 0x3) => format!("v{:x} ^= v{:x}", x, y),
+            (0x8, x, y, 0x4) => format!("v{:x} += v{:x}", x, y),
+            (0x8, x, y, 0x5) => format!("v{:x} -= v{:x}", x, y),
+            (0x8, x, y, 0x6) => format!("v{:x} >>= v{:x}", x, y),
+            (0x8, x, y, 0x7) => format!("v{:x} =- v{:x}", x, y),
+            (0x8, x, y, 0xE) => format!("v{:x} <<= v{:x}", x, y),
+            (0x9, x, y, 0x0) => format!("if v{:x} == v{:x} then", x, y),
+            (0xA, _, _, _) => format!("i := {}", name(nnn)),
+            (0xB, _, _, _) => format!("jump0 {}", name(nnn)),
+            (0xC, x, _, _) => format!("v{:x} := random {}", x, nn),
+            (0xD, x, y, n) => format!("sprite v{:x} v{:x} {}", x, y, n),
+            (0xE, x, 0x9, 0xE) => format!("if v{:x} -key then", x),
+            (0xE, x, 0xA, 0x1) => format!("if v{:x} key then", x),
+            (0xF, 0x0, 0x0, 0x0) => match long {
+                Some(addr) => format!("i := long {}", name(addr)),
+                None => "i := long".to_string(),
+            },
+            (0xF, n, 0x0, 0x1) => format!("plane {}", n),
+            (0xF, 0x0, 0x0, 0x2) => "audio".to_string(),
+            (0xF, x, 0x0, 0x7) => format!("v{:x} := delay", x),
+            (0xF, x, 0x0, 0xA) => format!("v{:x} := key", x),
+            (0xF, x, 0x1, 0x5) => format!("delay := v{:x}", x),
+            (0xF, x, 0x1, 0x8) => format!("buzzer := v{:x}", x),
+            (0xF, x, 0x1, 0xE) => format!("i += v{:x}", x),
+            (0xF, x, 0x2, 0x9) => format!("i := hex v{:x}", x),
+            (0xF, x, 0x3, 0x0) => format!("i := bighex v{:x}", x),
+            (0xF, x, 0x3, 0x3) => format!("bcd v{:x}", x),
+            (0xF, x, 0x3, 0xA) => format!("pitch := v{:x}", x),
+            (0xF, x, 0x5, 0x5) => format!("save v{:x}", x),
+            (0xF, x, 0x6, 0x5) => format!("load v{:x}", x),
+            (0xF, x, 0x7, 0x5) => format!("saveflags v{:x}", x),
+            (0xF, x, 0x8, 0x5) => format!("loadflags v{:x}", x),
+            _ => return None,
+        },
+    };
+    Some(text)
+}
+
+fn is_native(opcode: u16) -> bool {
+    opcode >> 12 == 0x0
+        && !(opcode & 0xFFF0 == 0x00C0
+            || opcode & 0xFFF0 == 0x00D0
+            || opcode == 0x00E0
+            || opcode == 0x00EE
+            || (0x00FB..=0x00FF).contains(&opcode))
+}
+
+/// A ROM split into code and data by following the control flow from the
+/// entry point.
+pub struct Disassembly<'a> {
+    rom: &'a [u8],
+    // Length of the instruction starting at each ROM offset, 0 for data and
+    // for the second half of an instruction.
+    code: Vec<usize>,
+    labels: BTreeSet<u16>,
+}
+
+impl<'a> Disassembly<'a> {
+    /// Disassembles a ROM loaded at the usual 0x200. Execution is followed
+    /// through jumps, calls and both outcomes of every skip; the base of a
+    /// BNNN jump table is treated as code. Anything never reached is data.
+    pub fn new(rom: &'a [u8]) -> Self {
+        let mut disassembly = Disassembly {
+            rom,
+            code: vec![0; rom.len()],
+            labels: BTreeSet::new(),
+        };
+        let start = Chip8::PC_START as u16;
+        disassembly.labels.insert(start);
+
+        let mut pending = vec![start];
+        while let Some(addr) = pending.pop() {
+            let offset = match disassembly.offset(addr) {
+                Some(offset) => offset,
+                None => continue,
+            };
+            if disassembly.code[offset] != 0 || offset + 1 >= rom.len() {
+                continue;
+            }
+            // Invalid opcodes and 0NNN machine code routines end the trace.
+            let opcode = disassembly.word(offset);
+            if mnemonic(opcode, Syntax::Cowgod).is_none() || is_native(opcode) {
+                continue;
+            }
+            let len = if opcode == 0xF000 { 4 } else { 2 };
+            if offset + len > rom.len() {
+                continue;
+            }
+            disassembly.code[offset] = len;
+
+            let next = addr.wrapping_add(len as u16);
+            let nnn = opcode & 0x0FFF;
+            match opcode >> 12 {
+                0x0 if opcode == 0x00EE || opcode == 0x00FD => {}
+                0x1 => {
+                    disassembly.labels.insert(nnn);
+                    pending.push(nnn);
+                }
+                0x2 => {
+                    disassembly.labels.insert(nnn);
+                    pending.push(nnn);
+                    pending.push(next);
+                }
+                0x3 | 0x4 | 0x5 | 0x9 | 0xE => {
+                    pending.push(next);
+                    let skipped = disassembly.offset(next).map_or(2, |next| {
+                        if next + 1 < rom.len() && disassembly.word(next) == 0xF000 {
+                            4
+                        } else {
+                            2
+                        }
+                    });
+                    pending.push(next.wrapping_add(skipped));
+                }
+                0xA => {
+                    disassembly.labels.insert(nnn);
+                    pending.push(next);
+                }
+                0xB => {
+                    disassembly.labels.insert(nnn);
+                    pending.push(nnn);
+                }
+                0xF if opcode == 0xF000 => {
+                    disassembly.labels.insert(disassembly.word(offset + 2));
+                    pending.push(next);
+                }
+                _ => pending.push(next),
+            }
+        }
+
+        // A jump into the middle of another instruction has no line to put
+        // its label on, so the target is shown as a number instead.
+        let mut hidden = vec![false; rom.len()];
+        let mut offset = 0;
+        while offset < rom.len() {
+            let len = disassembly.code[offset].max(1);
+            hidden[offset + 1..offset + len].fill(true);
+            offset += len;
+        }
+        disassembly.labels.retain(|&addr| {
+            (addr as usize)
+                .checked_sub(Chip8::PC_START)
+                .and_then(|offset| hidden.get(offset))
+                != Some(&true)
+        });
+        disassembly
+    }
+
+    /// A listing of the whole ROM with labels, addresses, raw bytes and
+    /// either a mnemonic or the data bytes on every line.
+    pub fn listing(&self, syntax: Syntax) -> String {
+        let mut out = String::new();
+        let name = |addr: u16| self.label_name(addr, syntax);
+        let mut offset = 0;
+        while offset < self.rom.len() {
+            let addr = (Chip8::PC_START + offset) as u16;
+            if self.labels.contains(&addr) {
+                match syntax {
+                    Syntax::Cowgod => writeln!(out, "{}:", name(addr)),
+                    Syntax::Octo => writeln!(out, ": {}", name(addr)),
+                }
+                .unwrap();
+            }
+
+            let len = self.code[offset];
+            if len > 0 {
+                let opcode = self.word(offset);
+                let long = if len == 4 {
+                    Some(self.word(offset + 2))
+                } else {
+                    None
+                };
+                let text = format_op(opcode, long, syntax, &name).unwrap();
+                self.line(
+                    &mut out,
+                    syntax,
+                    addr,
+                    &self.rom[offset..offset + len],
+                    &text,
+                );
+                offset += len;
+            } else {
+                // Runs of data end at the next label or code, eight bytes at most.
+                let mut end = offset + 1;
+                while end < self.rom.len()
+                    && end - offset < 8
+                    && self.code[end] == 0
+                    && !self.labels.contains(&((Chip8::PC_START + end) as u16))
+                {
+                    end += 1;
+                }
+                let data = &self.rom[offset..end];
+                let bytes: Vec<String> =
+                    data.iter().map(|&b| syntax.hex(u16::from(b), 2)).collect();
+                let text = match syntax {
+                    Syntax::Cowgod => format!("DB {}", bytes.join(", ")),
+                    Syntax::Octo => bytes.join(" "),
+                };
+                self.line(&mut out, syntax, addr, data, &text);
+                offset = end;
+            }
+        }
+        out
+    }
+
+    fn line(&self, out: &mut String, syntax: Syntax, addr: u16, bytes: &[u8], text: &str) {
+        let raw: String = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+        match syntax {
+            Syntax::Cowgod => writeln!(out, "{:03X}  {:<16}  {}", addr, raw, text),
+            Syntax::Octo => writeln!(out, "\t{:<40} # {:03X}  {}", text, addr, raw),
+        }
+        .unwrap();
+    }
+
+    // Labels are only emitted for addresses inside the ROM, anything else is
+    // shown as a number.
+    fn label_name(&self, addr: u16, syntax: Syntax) -> String {
+        if !self.labels.contains(&addr) || self.offset(addr).is_none() {
+            return syntax.hex(addr, 3);
+        }
+        match syntax {
+            Syntax::Octo if addr as usize == Chip8::PC_START => "main".to_string(),
+            Syntax::Cowgod => format!("L{:03X}", addr),
+            Syntax::Octo => format!("label-{:03X}", addr),
+        }
+    }
+
+    fn offset(&self, addr: u16) -> Option<usize> {
+        let offset = (addr as usize).checked_sub(Chip8::PC_START)?;
+        if offset < self.rom.len() {
+            Some(offset)
+        } else {
+            None
+        }
+    }
+
+    fn word(&self, offset: usize) -> u16 {
+        u16::from(self.rom[offset]) << 8 | u16::from(self.rom[offset + 1])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // LD I, 206; DRW V0, V1, 5; JP 204; then the 0 glyph as data.
+    const ROM: [u8; 11] = [
+        0xA2, 0x06, 0xD0, 0x15, 0x12, 0x04, 0xF0, 0x90, 0x90, 0x90, 0xF0,
+    ];
+
+    #[test]
+    fn cowgod_listing() {
+        assert_eq!(
+            Disassembly::new(&ROM).listing(Syntax::Cowgod),
+            "L200:\n\
+             200  A206              LD I, L206\n\
+             202  D015              DRW V0, V1, 5\n\
+             L204:\n\
+             204  1204              JP L204\n\
+             L206:\n\
+             206  F0909090F0        DB #F0, #90, #90, #90, #F0\n"
+        );
+    }
+
+    #[test]
+    fn octo_listing() {
+        assert_eq!(
+            Disassembly::new(&ROM).listing(Syntax::Octo),
+            ": main\n\
+             \ti := label-206                           # 200  A206\n\
+             \tsprite v0 v1 5                           # 202  D015\n\
+             : label-204\n\
+             \tjump label-204                           # 204  1204\n\
+             : label-206\n\
+             \t0xF0 0x90 0x90 0x90 0xF0                 # 206  F0909090F0\n"
+        );
+    }
+
+    #[test]
+    fn unreached_bytes_are_data() {
+        // EXIT, then a valid CLS that nothing jumps to.
+        let disassembly = Disassembly::new(&[0x00, 0xFD, 0x00, 0xE0]);
+        assert_eq!(disassembly.code, [2, 0, 0, 0]);
+    }
+
+    #[test]
+    fn skips_over_long_loads() {
+        // SE V0, 0; LD I, LONG 1234; EXIT. Skipping the F000 must not land
+        // on its operand, which would read as JP 234.
+        let disassembly = Disassembly::new(&[0x30, 0x00, 0xF0, 0x00, 0x12, 0x34, 0x00, 0xFD]);
+        assert_eq!(disassembly.code, [2, 0, 4, 0, 0, 0, 2, 0]);
+        assert!(disassembly
+            .listing(Syntax::Cowgod)
+            .contains("LD I, LONG #1234"));
+    }
+
+    #[test]
+    fn jump_tables_are_code() {
+        // JP V0, 204; two bytes of data; CLS; EXIT.
+        let disassembly = Disassembly::new(&[0xB2, 0x04, 0xFF, 0xFF, 0x00, 0xE0, 0x00, 0xFD]);
+        assert_eq!(disassembly.code, [2, 0, 0, 0, 2, 0, 2, 0]);
+        assert!(disassembly.listing(Syntax::Cowgod).contains("L204:\n"));
+    }
+
+    #[test]
+    fn jumps_into_instructions_are_numbers() {
+        let listing = Disassembly::new(&[0x12, 0x01, 0x00, 0xE0]).listing(Syntax::Cowgod);
+        assert!(listing.contains("JP #201"));
+        assert!(!listing.contains("L201"));
+    }
+}
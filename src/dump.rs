@@ -0,0 +1,147 @@
+//! Encoding the screen as text or image files.
+
+use crate::chip8::Chip8;
+
+/// File format for `dump`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DumpFormat {
+    /// One character per pixel and a newline after every row.
+    Ascii,
+    /// Binary portable bitmap, with a pixel set if any plane is lit.
+    Pbm,
+    /// Indexed PNG with one palette entry per plane combination.
+    Png,
+}
+
+impl DumpFormat {
+    pub fn from_name(name: &str) -> Option<DumpFormat> {
+        match name.to_ascii_lowercase().as_str() {
+            "ascii" => Some(DumpFormat::Ascii),
+            "pbm" => Some(DumpFormat::Pbm),
+            "png" => Some(DumpFormat::Png),
+            _ => None,
+        }
+    }
+}
+
+// Character for each combination of lit bitplanes.
+const ASCII_PIXELS: [u8; 4] = [b'.', b'#', b'o', b'@'];
+// RGB for each combination of lit bitplanes.
+const PNG_PALETTE: [u8; 12] = [
+    0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xAA, 0xAA, 0xAA, 0x55, 0x55, 0x55,
+];
+
+/// Encodes the current screen in `format`.
+pub fn dump(chip8: &Chip8, format: DumpFormat) -> Vec<u8> {
+    let (width, height) = (chip8.width(), chip8.height());
+    let pixels = chip8.framebuffer();
+    match format {
+        DumpFormat::Ascii => {
+            let mut out = Vec::with_capacity((width + 1) * height);
+            for row in pixels.chunks(width) {
+                out.extend(row.iter().map(|&pix| ASCII_PIXELS[(pix & 0x3) as usize]));
+                out.push(b'\n');
+            }
+            out
+        }
+        DumpFormat::Pbm => {
+            let mut out = format!("P4\n{} {}\n", width, height).into_bytes();
+            for row in pixels.chunks(width) {
+                for byte in row.chunks(8) {
+                    let bits = byte
+                        .iter()
+                        .enumerate()
+                        .fold(0u8, |bits, (n, &pix)| bits | ((pix != 0) as u8) << (7 - n));
+                    out.push(bits);
+                }
+            }
+            out
+        }
+        DumpFormat::Png => {
+            let mut out = Vec::new();
+            let mut encoder = png::Encoder::new(&mut out, width as u32, height as u32);
+            encoder.set_color(png::ColorType::Indexed);
+            encoder.set_depth(png::BitDepth::Eight);
+            encoder.set_palette(&PNG_PALETTE[..]);
+            let data: Vec<u8> = pixels.iter().map(|pix| pix & 0x3).collect();
+            // Writing to a Vec can't fail.
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(&data).unwrap();
+            writer.finish().unwrap();
+            out
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A machine that has executed all of `rom`.
+    fn screen(mut chip8: Chip8, rom: &[u8], steps: usize) -> Chip8 {
+        chip8.load_rom(rom).unwrap();
+        for _ in 0..steps {
+            chip8.step().unwrap();
+        }
+        chip8
+    }
+
+    #[test]
+    fn ascii_maps_planes_to_characters() {
+        // F301 selects both planes, then DRW V0, V0, 1 at I = 206 draws
+        // 01010000 on the first plane and 00110000 on the second.
+        let rom = [0xF3, 0x01, 0xA2, 0x06, 0xD0, 0x01, 0x50, 0x30];
+        let ascii = dump(&screen(Chip8::new_xo_chip(), &rom, 3), DumpFormat::Ascii);
+        assert_eq!(ascii.len(), 65 * 32);
+        assert_eq!(&ascii[..5], b".#o@.");
+        assert_eq!(&ascii[63..66], b".\n.");
+    }
+
+    #[test]
+    fn pbm_packs_rows_most_significant_bit_first() {
+        // DRW V0, V0, 2 at I = 206 draws 10000001 over 01000000.
+        let rom = [0xA2, 0x06, 0xD0, 0x02, 0x00, 0x00, 0x81, 0x40];
+        let pbm = dump(&screen(Chip8::new(), &rom, 2), DumpFormat::Pbm);
+        let header = b"P4\n64 32\n";
+        assert_eq!(&pbm[..header.len()], header);
+        let rows = &pbm[header.len()..];
+        assert_eq!(rows.len(), 8 * 32);
+        assert_eq!(&rows[..2], &[0x81, 0x00]);
+        assert_eq!(&rows[8..10], &[0x40, 0x00]);
+        assert!(rows[16..].iter().all(|&byte| byte == 0));
+    }
+
+    #[test]
+    fn png_header_matches_resolution() {
+        let ihdr = |png: &[u8]| {
+            assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+            assert_eq!(&png[12..16], b"IHDR");
+            let size = |n: usize| u32::from_be_bytes([png[n], png[n + 1], png[n + 2], png[n + 3]]);
+            // Width, height, bit depth and colour type.
+            (size(16), size(20), png[24], png[25])
+        };
+        let lores = dump(&Chip8::new(), DumpFormat::Png);
+        assert_eq!(ihdr(&lores), (64, 32, 8, 3));
+        let hires = dump(&screen(Chip8::new(), &[0x00, 0xFF], 1), DumpFormat::Png);
+        assert_eq!(ihdr(&hires), (128, 64, 8, 3));
+    }
+
+    #[test]
+    fn dumps_hires_screens() {
+        // 00FF, then DRW V0, V1, 1 with V0 = 127 at I = 0.
+        let rom = [0x00, 0xFF, 0x60, 0x7F, 0xD0, 0x11];
+        let chip8 = screen(Chip8::new(), &rom, 3);
+        let ascii = dump(&chip8, DumpFormat::Ascii);
+        assert_eq!(ascii.len(), 129 * 64);
+        // The 0 glyph's top row wraps from the right edge to the left.
+        assert_eq!(&ascii[..4], b"###.");
+        assert_eq!(&ascii[126..129], b".#\n");
+
+        let pbm = dump(&chip8, DumpFormat::Pbm);
+        let header = b"P4\n128 64\n";
+        assert_eq!(&pbm[..header.len()], header);
+        assert_eq!(pbm.len(), header.len() + 16 * 64);
+        assert_eq!(pbm[header.len()], 0xE0);
+        assert_eq!(pbm[header.len() + 15], 0x01);
+    }
+}
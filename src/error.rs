@@ -0,0 +1,146 @@
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+/// Reasons the machine can stop executing a program.
+///
+/// Errors raised while executing an instruction carry the address it was
+/// fetched from and its raw opcode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Chip8Error {
+    /// The ROM does not fit in memory above the program start address.
+    RomTooLarge { size: usize, max: usize },
+    /// The program counter left addressable memory.
+    PcOutOfBounds { pc: u16 },
+    /// 0NNN calls a native COSMAC VIP routine, which cannot be emulated.
+    MachineRoutine { pc: u16, opcode: u16 },
+    /// The opcode does not decode to any known instruction.
+    InvalidOpcode { pc: u16, opcode: u16 },
+    /// 2NNN was executed with every stack slot in use.
+    StackOverflow { pc: u16, opcode: u16 },
+    /// 00EE was executed with an empty stack.
+    StackUnderflow { pc: u16, opcode: u16 },
+    /// The instruction reads or writes memory past the end of RAM.
+    MemoryOutOfBounds { pc: u16, opcode: u16, addr: usize },
+}
+
+impl Display for Chip8Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Chip8Error::RomTooLarge { size, max } => {
+                write!(f, "ROM is {} bytes, at most {} fit in memory", size, max)
+            }
+            Chip8Error::PcOutOfBounds { pc } => {
+                write!(f, "program counter {:#05X} is outside memory", pc)
+            }
+            Chip8Error::MachineRoutine { pc, opcode } => write!(
+                f,
+                "{:#05X}: {:04X} calls a machine code routine, which is not supported",
+                pc, opcode
+            ),
+            Chip8Error::InvalidOpcode { pc, opcode } => {
+                write!(f, "{:#05X}: {:04X} is not a valid instruction", pc, opcode)
+            }
+            Chip8Error::StackOverflow { pc, opcode } => {
+                write!(f, "{:#05X}: {:04X} overflowed the call stack", pc, opcode)
+            }
+            Chip8Error::StackUnderflow { pc, opcode } => write!(
+                f,
+                "{:#05X}: {:04X} returned with an empty call stack",
+                pc, opcode
+            ),
+            Chip8Error::MemoryOutOfBounds { pc, opcode, addr } => write!(
+                f,
+                "{:#05X}: {:04X} accessed {:#X}, which is outside memory",
+                pc, opcode, addr
+            ),
+        }
+    }
+}
+
+impl Error for Chip8Error {}
+
+/// Reasons a save state can be rejected by `Chip8::load_state`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateError {
+    /// The data does not start with the save state magic.
+    BadMagic,
+    /// The state was written by an incompatible version of the format.
+    UnsupportedVersion(u8),
+    /// The data ends before the state is complete.
+    Truncated,
+    /// A field holds a value the machine cannot be in.
+    Corrupt,
+}
+
+impl Display for StateError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            StateError::BadMagic => write!(f, "not a save state"),
+            StateError::UnsupportedVersion(version) => {
+                write!(f, "unsupported save state version {}", version)
+            }
+            StateError::Truncated => write!(f, "save state is truncated"),
+            StateError::Corrupt => write!(f, "save state is corrupt"),
+        }
+    }
+}
+
+impl Error for StateError {}
+
+/// Reasons an input movie can't be played back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MovieError {
+    /// The data does not start with the movie magic.
+    BadMagic,
+    /// The movie was written by an incompatible version of the format.
+    UnsupportedVersion(u8),
+    /// The data ends before the movie is complete.
+    Truncated,
+    /// A field holds a value that can't be played back.
+    Corrupt,
+    /// The movie was recorded with a different ROM.
+    WrongRom,
+}
+
+impl Display for MovieError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            MovieError::BadMagic => write!(f, "not a movie"),
+            MovieError::UnsupportedVersion(version) => {
+                write!(f, "unsupported movie version {}", version)
+            }
+            MovieError::Truncated => write!(f, "movie is truncated"),
+            MovieError::Corrupt => write!(f, "movie is corrupt"),
+            MovieError::WrongRom => write!(f, "movie was recorded with a different ROM"),
+        }
+    }
+}
+
+impl Error for MovieError {}
+
+impl From<StateError> for MovieError {
+    fn from(err: StateError) -> Self {
+        match err {
+            StateError::BadMagic => MovieError::BadMagic,
+            StateError::UnsupportedVersion(version) => MovieError::UnsupportedVersion(version),
+            StateError::Truncated => MovieError::Truncated,
+            StateError::Corrupt => MovieError::Corrupt,
+        }
+    }
+}
+
+/// An assembly error, located by 1-based line and column.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl Display for AsmError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl Error for AsmError {}
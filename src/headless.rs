@@ -0,0 +1,122 @@
+//! Running the machine without a front end.
+
+use crate::chip8::Chip8;
+use crate::error::Chip8Error;
+use crate::movie::Movie;
+use crate::scheduler::{Scheduler, Speed};
+
+/// Runs the machine without a terminal until `cycles` instructions have
+/// executed or the program stops on its own: by exiting, by jumping to
+/// itself, or by waiting for a key that will never be pressed. Timers still
+/// tick once per frame's worth of instructions.
+pub fn run(chip8: &mut Chip8, speed: Speed, cycles: u64) -> Result<(), Chip8Error> {
+    run_with(chip8, speed, cycles, |_| {})
+}
+
+/// Like `run`, calling `after_frame` once each frame has executed and the
+/// timers have ticked.
+pub fn run_with<F>(
+    chip8: &mut Chip8,
+    speed: Speed,
+    cycles: u64,
+    mut after_frame: F,
+) -> Result<(), Chip8Error>
+where
+    F: FnMut(&Chip8),
+{
+    let mut scheduler = Scheduler::new(speed);
+    let mut executed = 0;
+    while executed < cycles {
+        if chip8.halted() || chip8.is_spinning() || chip8.is_waiting_for_key() {
+            break;
+        }
+        let instructions = u64::from(scheduler.instructions_for_frame()).min(cycles - executed);
+        chip8.run_frame(instructions as u32)?;
+        executed += instructions;
+        after_frame(chip8);
+    }
+    Ok(())
+}
+
+/// Plays `movie` back from the first frame to the last, at the speed it was
+/// recorded at, on a machine from `Movie::machine`. Unlike `run`, it doesn't
+/// stop early when the program waits for a key, since a later frame may
+/// press one.
+pub fn play(chip8: &mut Chip8, movie: &Movie) -> Result<(), Chip8Error> {
+    play_with(chip8, movie, |_| {})
+}
+
+/// Like `play`, calling `after_frame` once each frame has executed and the
+/// timers have ticked.
+pub fn play_with<F>(chip8: &mut Chip8, movie: &Movie, mut after_frame: F) -> Result<(), Chip8Error>
+where
+    F: FnMut(&Chip8),
+{
+    let mut scheduler = Scheduler::new(movie.speed());
+    for frame in 0..movie.frames() {
+        movie.play_frame(chip8, frame);
+        chip8.run_frame(scheduler.instructions_for_frame())?;
+        after_frame(chip8);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPEED: Speed = Speed::InstructionsPerFrame(10);
+
+    fn machine(mut chip8: Chip8, rom: &[u8]) -> Chip8 {
+        chip8.load_rom(rom).unwrap();
+        chip8
+    }
+
+    #[test]
+    fn stops_when_halted() {
+        // ADD V0, 1 then EXIT.
+        let mut chip8 = machine(Chip8::new(), &[0x70, 0x01, 0x00, 0xFD]);
+        run(&mut chip8, SPEED, u64::MAX).unwrap();
+        assert!(chip8.halted());
+        assert_eq!(chip8.cycles(), 2);
+    }
+
+    #[test]
+    fn stops_when_spinning() {
+        // ADD V0, 1 then JP 202.
+        let mut chip8 = machine(Chip8::new(), &[0x70, 0x01, 0x12, 0x02]);
+        run(&mut chip8, SPEED, u64::MAX).unwrap();
+        assert_eq!(chip8.pc(), 0x202);
+        assert_eq!(chip8.v()[0], 1);
+    }
+
+    #[test]
+    fn runs_past_xo_chip_jumps_that_look_like_spinning() {
+        // ADD V0, 1 up to 1234, which holds JP 234 back into the ADDs.
+        let mut rom = [0x70, 0x01].repeat((0x1234 - 0x200) / 2);
+        rom.extend(&[0x12, 0x34]);
+        let mut chip8 = machine(Chip8::new_xo_chip(), &rom);
+        run(&mut chip8, SPEED, 3000).unwrap();
+        assert_eq!(chip8.cycles(), 3000);
+    }
+
+    #[test]
+    fn stops_when_waiting_for_key() {
+        // LD V1, K.
+        let mut chip8 = machine(Chip8::new(), &[0xF1, 0x0A]);
+        run(&mut chip8, SPEED, u64::MAX).unwrap();
+        assert!(chip8.is_waiting_for_key());
+        assert_eq!(chip8.cycles(), 1);
+    }
+
+    #[test]
+    fn stops_after_cycles() {
+        // ADD V0, 1 then JP 200.
+        let mut chip8 = machine(Chip8::new(), &[0x70, 0x01, 0x12, 0x00]);
+        let mut frames = 0;
+        run_with(&mut chip8, SPEED, 25, |_| frames += 1).unwrap();
+        assert_eq!(chip8.cycles(), 25);
+        assert_eq!(chip8.v()[0], 13);
+        assert_eq!(frames, 3);
+    }
+}
@@ -0,0 +1,66 @@
+/// State of the 16-key hexadecimal keypad.
+pub struct Keypad {
+    down: [bool; Keypad::NUM_KEYS],
+    released: Option<u8>,
+}
+
+impl Keypad {
+    pub const NUM_KEYS: usize = 16;
+
+    pub fn new() -> Self {
+        Keypad {
+            down: [false; Keypad::NUM_KEYS],
+            released: None,
+        }
+    }
+
+    pub fn press(&mut self, key: u8) {
+        self.down[(key & 0x0F) as usize] = true;
+    }
+
+    pub fn release(&mut self, key: u8) {
+        let key = key & 0x0F;
+        if self.down[key as usize] {
+            self.down[key as usize] = false;
+            self.released = Some(key);
+        }
+    }
+
+    pub fn is_down(&self, key: u8) -> bool {
+        self.down[(key & 0x0F) as usize]
+    }
+
+    pub fn has_released(&self) -> bool {
+        self.released.is_some()
+    }
+
+    // Held keys as a bitmask, and the pending release.
+    pub(crate) fn snapshot(&self) -> (u16, Option<u8>) {
+        let down = self
+            .down
+            .iter()
+            .enumerate()
+            .fold(0, |bits, (key, &down)| bits | (down as u16) << key);
+        (down, self.released)
+    }
+
+    pub(crate) fn restore(down: u16, released: Option<u8>) -> Self {
+        let mut keypad = Keypad::new();
+        for (key, state) in keypad.down.iter_mut().enumerate() {
+            *state = down & (1 << key) != 0;
+        }
+        keypad.released = released;
+        keypad
+    }
+
+    /// Returns the most recently released key, if any, and forgets it.
+    pub fn take_released(&mut self) -> Option<u8> {
+        self.released.take()
+    }
+}
+
+impl Default for Keypad {
+    fn default() -> Self {
+        Keypad::new()
+    }
+}
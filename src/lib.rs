@@ -0,0 +1,25 @@
+//! A CHIP-8 interpreter core that can be driven by any front end.
+
+pub mod asm;
+pub mod audio;
+pub mod capture;
+mod chip8;
+mod debugger;
+pub mod disasm;
+pub mod dump;
+mod error;
+pub mod headless;
+mod keypad;
+pub mod movie;
+mod quirks;
+mod random;
+mod rewind;
+mod scheduler;
+
+pub use crate::chip8::Chip8;
+pub use crate::debugger::{Debugger, Stop, Watchpoint};
+pub use crate::error::{AsmError, Chip8Error, MovieError, StateError};
+pub use crate::keypad::Keypad;
+pub use crate::quirks::{MemoryIncrement, Quirks};
+pub use crate::rewind::Rewind;
+pub use crate::scheduler::{Scheduler, Speed};
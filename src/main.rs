@@ -1,277 +1,501 @@
-use std::fmt::{Display, Error, Formatter};
+mod debug;
+mod phosphor;
+mod recording;
+mod screen;
+mod terminal;
+mod theme;
+
+use recording::{CaptureFormat, Recording};
+use rustichip8::asm;
+use rustichip8::audio::{AudioSink, Bell, Pcm, SquareWave, Tone, Wav};
+use rustichip8::capture::DEFAULT_PALETTE;
+use rustichip8::disasm::{Disassembly, Syntax};
+use rustichip8::dump::{self, DumpFormat};
+use rustichip8::headless;
+use rustichip8::movie::Movie;
+use rustichip8::{Chip8, Quirks, Scheduler, Speed};
+use screen::{RenderMode, Screen};
+use std::env;
 use std::fs;
-use std::io::{stdout, Write};
-use std::path::Path;
-use std::{env, time};
-use termion::raw::IntoRawMode;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::FromStr;
+use terminal::Input;
+use theme::{ColorDepth, Theme};
 
-struct Cpu {
-    pc: u16,
-    i: u16,
-    v: [u8; Cpu::NUM_REGISTERS],
-    delay_timer: u8,
-    sound_timer: u8,
-    stack: [u16; Cpu::MAX_STACK],
-    sp: usize,
-    ram: [u8; Cpu::RAM_SIZE],
-    vram: [u8; Cpu::VRAM_SIZE],
-}
+const USAGE: &str = "Usage: rustichip8 [--xo-chip] [--quirks classic|vip|chip48|schip|xochip] \
+                     [--ipf N | --hz N] [--seed N] [--debug] [--render block|half|braille] [--double-width] \
+                     [--theme octo|amber|green|#bg,#fg[,#plane2,#both]] [--colors 8|256|truecolor] \
+                     [--persistence FRAMES] \
+                     [--audio bell|none|wav:file|pcm:file] [--tone HZ] [--volume PERCENT] \
+                     [--record-input movie | --replay movie] \
+                     [--record out.gif|out.y4m|out.ppm] [--scale N] \
+                     [--headless [--cycles N] [--dump ascii|pbm|png] [--output file]] rom.ch8
+       rustichip8 disasm [--syntax cowgod|octo] rom.ch8
+       rustichip8 asm source.asm [-o rom.ch8]";
 
-impl Cpu {
-    const PC_START: usize = 0x200;
-    const OP_SIZE: u16 = 2;
-    const RAM_SIZE: usize = 4096;
-    const VRAM_WIDTH: usize = 64;
-    const VRAM_HEIGHT: usize = 32;
-    const VRAM_SIZE: usize = Cpu::VRAM_HEIGHT * Cpu::VRAM_WIDTH;
-    const NUM_REGISTERS: usize = 16;
-    const MAX_STACK: usize = 24;
-    const FONT_SET: [u8; 80] = [
-        0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-        0x20, 0x60, 0x20, 0x20, 0x70, // 1
-        0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
-        0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
-        0x90, 0x90, 0xF0, 0x10, 0x10, // 4
-        0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
-        0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
-        0xF0, 0x10, 0x20, 0x40, 0x40, // 7
-        0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
-        0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
-        0xF0, 0x90, 0xF0, 0x90, 0x90, // A
-        0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
-        0xF0, 0x80, 0x80, 0x80, 0xF0, // C
-        0xE0, 0x90, 0x90, 0x90, 0xE0, // D
-        0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
-        0xF0, 0x80, 0xF0, 0x80, 0x80, // F
-    ];
+struct Options {
+    rom: PathBuf,
+    xo_chip: bool,
+    quirks: Option<Quirks>,
+    speed: Speed,
+    seed: Option<u64>,
+    debug: bool,
+    render: RenderMode,
+    double_width: bool,
+    theme: Option<Theme>,
+    color_depth: Option<ColorDepth>,
+    persistence: u8,
+    headless: bool,
+    cycles: u64,
+    dump: DumpFormat,
+    output: Option<PathBuf>,
+    audio: Option<AudioOutput>,
+    tone: f64,
+    volume: u8,
+    record_input: Option<PathBuf>,
+    replay: Option<PathBuf>,
+    record: Option<PathBuf>,
+    scale: u16,
+}
 
-    pub fn new() -> Self {
-        let mut cpu = Cpu {
-            pc: Cpu::PC_START as u16,
-            i: 0,
-            v: [0; Cpu::NUM_REGISTERS],
-            delay_timer: 0,
-            sound_timer: 0,
-            stack: [0; Cpu::MAX_STACK],
-            sp: 0,
-            ram: [0; Cpu::RAM_SIZE],
-            vram: [0; Cpu::VRAM_SIZE],
-        };
-        cpu.ram[..Cpu::FONT_SET.len()].copy_from_slice(&Cpu::FONT_SET);
-        cpu
-    }
+/// Where the sound timer's tone goes.
+enum AudioOutput {
+    None,
+    Bell,
+    Wav(PathBuf),
+    Pcm(PathBuf),
+}
 
-    pub fn load_rom(&mut self, rom: &[u8]) {
-        self.ram[Cpu::PC_START..][..rom.len()].copy_from_slice(rom);
+impl AudioOutput {
+    fn from_name(name: &str) -> Option<AudioOutput> {
+        if let Some(path) = name.strip_prefix("wav:") {
+            return Some(AudioOutput::Wav(PathBuf::from(path)));
+        }
+        if let Some(path) = name.strip_prefix("pcm:") {
+            return Some(AudioOutput::Pcm(PathBuf::from(path)));
+        }
+        match name {
+            "none" => Some(AudioOutput::None),
+            "bell" => Some(AudioOutput::Bell),
+            _ => None,
+        }
     }
+}
 
-    pub fn run(&mut self) {
-        let mut timer: usize = 0;
-        let mut stdout = stdout().into_raw_mode().unwrap();
-        let mut frame = String::new();
-        frame.reserve(Cpu::VRAM_SIZE + Cpu::VRAM_HEIGHT);
-        loop {
-            let op = self.fetch_op();
-            self.decode_op(op);
-            //println!("{}", self);
-            if timer % 10 == 0 {
-                self.update_timers();
-            }
-            timer += 1;
-
-            frame.clear();
-            for y in 0..Cpu::VRAM_HEIGHT {
-                for x in 0..Cpu::VRAM_WIDTH {
-                    let pix = if self.vram[x + y * Cpu::VRAM_WIDTH] == 1 {
-                        '█'
-                    } else {
-                        ' '
-                    };
-                    frame.push(pix);
+impl Options {
+    fn parse(args: &[String]) -> Result<Options, String> {
+        let mut rom = None;
+        let mut xo_chip = false;
+        let mut quirks = None;
+        let mut speed = Speed::default();
+        let mut seed = None;
+        let mut debug = false;
+        let mut render = RenderMode::default();
+        let mut double_width = false;
+        let mut theme = None;
+        let mut color_depth = None;
+        let mut persistence = 0;
+        let mut headless = false;
+        let mut cycles = u64::MAX;
+        let mut dump = DumpFormat::Ascii;
+        let mut output = None;
+        let mut audio = None;
+        let mut tone = SquareWave::DEFAULT_FREQUENCY;
+        let mut volume = (SquareWave::DEFAULT_VOLUME * 100.0) as u8;
+        let mut record_input = None;
+        let mut replay = None;
+        let mut record = None;
+        let mut scale = 4;
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--xo-chip" => xo_chip = true,
+                "--debug" => debug = true,
+                "--double-width" => double_width = true,
+                "--theme" => {
+                    let name = args.next().ok_or("--theme needs a name or colours")?;
+                    theme = Some(Theme::from_name(name)?);
                 }
-
-                frame.push_str("\r\n");
+                "--colors" => {
+                    let name = args.next().ok_or("--colors needs a colour depth")?;
+                    let depth = ColorDepth::from_name(name)
+                        .ok_or_else(|| format!("Unknown colour depth {}", name))?;
+                    color_depth = Some(depth);
+                }
+                "--persistence" => persistence = parse_number(arg, args.next())?,
+                "--render" => {
+                    let name = args.next().ok_or("--render needs a mode")?;
+                    render = RenderMode::from_name(name)
+                        .ok_or_else(|| format!("Unknown render mode {}", name))?;
+                }
+                "--headless" => headless = true,
+                "--cycles" => cycles = parse_number(arg, args.next())?,
+                "--dump" => {
+                    let name = args.next().ok_or("--dump needs a format")?;
+                    dump = DumpFormat::from_name(name)
+                        .ok_or_else(|| format!("Unknown dump format {}", name))?;
+                }
+                "--output" => {
+                    let path = args.next().ok_or("--output needs a path")?;
+                    output = Some(PathBuf::from(path));
+                }
+                "--audio" => {
+                    let name = args.next().ok_or("--audio needs an output")?;
+                    let output = AudioOutput::from_name(name)
+                        .ok_or_else(|| format!("Unknown audio output {}", name))?;
+                    audio = Some(output);
+                }
+                "--record-input" => {
+                    let path = args.next().ok_or("--record-input needs a path")?;
+                    record_input = Some(PathBuf::from(path));
+                }
+                "--replay" => {
+                    let path = args.next().ok_or("--replay needs a path")?;
+                    replay = Some(PathBuf::from(path));
+                }
+                "--record" => {
+                    let path = PathBuf::from(args.next().ok_or("--record needs a path")?);
+                    if CaptureFormat::from_path(&path).is_none() {
+                        return Err(format!(
+                            "Can't record to {}, use .gif, .y4m or .ppm",
+                            path.display()
+                        ));
+                    }
+                    record = Some(path);
+                }
+                "--scale" => scale = parse_number(arg, args.next())?,
+                "--tone" => tone = parse_number(arg, args.next())?,
+                "--volume" => volume = parse_number(arg, args.next())?,
+                "--quirks" => {
+                    let name = args.next().ok_or("--quirks needs a preset name")?;
+                    let preset = Quirks::from_name(name)
+                        .ok_or_else(|| format!("Unknown quirks preset {}", name))?;
+                    quirks = Some(preset);
+                }
+                "--ipf" => speed = Speed::InstructionsPerFrame(parse_number(arg, args.next())?),
+                "--hz" => speed = Speed::Hertz(parse_number(arg, args.next())?),
+                "--seed" => seed = Some(parse_number(arg, args.next())?),
+                flag if flag.starts_with("--") => return Err(format!("Unknown option {}", flag)),
+                path if rom.is_none() => rom = Some(PathBuf::from(path)),
+                _ => return Err("Only one ROM may be given".to_string()),
             }
-            write!(
-                stdout,
-                "{}{}{}",
-                termion::clear::All,
-                termion::cursor::Hide,
-                frame
-            )
-            .unwrap();
-            stdout.flush().unwrap();
-            std::thread::sleep(time::Duration::from_millis(1000 / 600))
         }
+        let rom = rom.ok_or_else(|| "No ROM given".to_string())?;
+        if !speed.is_valid() {
+            let max = Speed::MAX_INSTRUCTIONS_PER_FRAME;
+            return Err(match speed {
+                Speed::InstructionsPerFrame(_) => format!("--ipf must be from 1 to {}", max),
+                Speed::Hertz(_) => {
+                    format!("--hz must be from 1 to {}", max * Scheduler::FRAME_RATE)
+                }
+            });
+        }
+        if record_input.is_some() && (replay.is_some() || headless) {
+            return Err("--record-input needs the terminal and no --replay".to_string());
+        }
+        if debug && (record_input.is_some() || replay.is_some()) {
+            return Err("--debug can't record or replay movies".to_string());
+        }
+        Ok(Options {
+            rom,
+            xo_chip,
+            quirks,
+            speed,
+            seed,
+            debug,
+            render,
+            double_width,
+            theme,
+            color_depth,
+            persistence,
+            headless,
+            cycles,
+            dump,
+            output,
+            audio,
+            tone,
+            volume: volume.min(100),
+            record_input,
+            replay,
+            record,
+            // Keeps the largest frame within what GIF can describe.
+            scale: scale.clamp(1, 64),
+        })
     }
+}
 
-    fn fetch_op(&mut self) -> (u8, usize, usize, usize) {
-        let pc = self.pc as usize;
-        let b1 = self.ram[pc];
-        let b2 = self.ram[pc + 1];
-
-        self.pc += Cpu::OP_SIZE;
+fn parse_number<T: FromStr>(flag: &str, value: Option<&String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("{} needs a number", flag))?;
+    value
+        .parse()
+        .map_err(|_| format!("{} needs a number, got {}", flag, value))
+}
 
-        (
-            (b1 & 0xF0) >> 4,
-            (b1 & 0x0F) as usize,
-            ((b2 & 0xF0) >> 4) as usize,
-            (b2 & 0x0F) as usize,
-        )
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("disasm") => disasm(&args[1..]),
+        Some("asm") => assemble(&args[1..]),
+        _ => run(&args),
     }
+}
 
-    fn decode_op(&mut self, op: (u8, usize, usize, usize)) {
-        match op {
-            (0x0, 0x0, 0xE, 0x0) => self.vram = [0; Cpu::VRAM_SIZE],
-            (0x0, 0x0, 0xE, 0xE) => {
-                self.sp -= 1;
-                self.pc = self.stack[self.sp];
-            }
-            (0x0, _, _, _) => unimplemented!("Deprecated op"),
-            (0x1, n1, n2, n3) => self.pc = Cpu::n3u16(n1, n2, n3),
-            (0x2, n1, n2, n3) => {
-                self.stack[self.sp] = self.pc;
-                self.sp += 1;
-                self.pc = Cpu::n3u16(n1, n2, n3);
-            }
-            (0x3, vx, n1, n2) => {
-                if self.v[vx] == Cpu::n2u8(n1, n2) {
-                    self.pc += Cpu::OP_SIZE;
-                }
-            }
-            (0x4, vx, n1, n2) => {
-                if self.v[vx] != Cpu::n2u8(n1, n2) {
-                    self.pc += Cpu::OP_SIZE;
-                }
-            }
-            (0x5, vx, vy, 0x0) => {
-                if self.v[vx] == self.v[vy] {
-                    self.pc += Cpu::OP_SIZE;
-                }
-            }
-            (0x6, vx, n1, n2) => self.v[vx] = Cpu::n2u8(n1, n2),
-            (0x7, vx, n1, n2) => self.v[vx] += Cpu::n2u8(n1, n2),
-            (0x8, vx, vy, 0x0) => self.v[vx] = self.v[vy],
-            (0x8, vx, vy, 0x1) => self.v[vx] |= self.v[vy],
-            (0x8, vx, vy, 0x2) => self.v[vx] &= self.v[vy],
-            (0x8, vx, vy, 0x3) => self.v[vx] ^= self.v[vy],
-            (0x8, vx, vy, 0x4) => {
-                let res = self.v[vx] as u16 + self.v[vy] as u16;
-                self.v[0xF] = if res > 0xFF { 1 } else { 0 };
-                self.v[vx] = res as u8;
-            }
-            (0x8, vx, vy, 0x5) => {
-                let res = self.v[vx] as i8 - self.v[vy] as i8;
-                self.v[0xF] = if res < 0 { 1 } else { 0 };
-                self.v[vx] = res as u8;
-            }
-            (0x8, vx, _, 0x6) => {
-                self.v[0xF] = self.v[vx] & 0x01;
-                self.v[vx] >>= 1;
-            }
-            (0x8, vx, _, 0xE) => {
-                self.v[0xF] = self.v[vx] & 0x80;
-                self.v[vx] <<= 1;
-            }
-            (0x9, vx, vy, 0x0) => {
-                if self.v[vx] != self.v[vy] {
-                    self.pc += Cpu::OP_SIZE;
-                }
-            }
-            (0xA, n1, n2, n3) => self.i = Cpu::n3u16(n1, n2, n3),
-            (0xB, n1, n2, n3) => self.pc = Cpu::n3u16(n1, n2, n3) + u16::from(self.v[0]),
-            (0xC, vx, n1, n2) => self.v[vx] = rand::random::<u8>() & Cpu::n2u8(n1, n2),
-            (0xD, vx, vy, n) => {
-                let sprite = &self.ram[self.i as usize..][..n];
-                let x = self.v[vx] as usize;
-                let y = self.v[vy] as usize;
+fn usage_error(err: &str) -> ! {
+    eprintln!("{}\n{}", err, USAGE);
+    process::exit(2);
+}
 
-                self.v[0xF] = 0;
-                for h in 0..sprite.len() {
-                    for w in 0..8 {
-                        let pix = (sprite[h] >> (7 - w)) & 0x01;
-                        let pos = (x + w) % Cpu::VRAM_WIDTH
-                            + ((y + h) % Cpu::VRAM_HEIGHT) * Cpu::VRAM_WIDTH;
-                        self.v[0xF] |= self.vram[pos] & pix;
-                        self.vram[pos] ^= pix;
-                    }
-                }
-            }
-            (0xE, vx, 0x9, 0xE) => {
-                //                if let Key::Char(c) = stdin().keys().next().unwrap().unwrap() {
-                //                    if c == (self.v[vx] + 48) as char {
-                //                        self.pc += Cpu::OP_SIZE;
-                //                    }
-                //                }
-            }
-            (0xE, vx, 0xA, 0x1) => {
-                //                if let Key::Char(c) = stdin().keys().next().unwrap().unwrap() {
-                //                    if c == (self.v[vx] + 48) as char {
-                //                        self.pc -= Cpu::OP_SIZE;
-                //                    }
-                //                }
-                self.pc += Cpu::OP_SIZE;
-            }
-            (0xF, vx, 0x0, 0x7) => self.v[vx] = self.delay_timer,
-            (0xF, vx, 0x1, 0x5) => self.delay_timer = self.v[vx],
-            (0xF, vx, 0x1, 0x8) => self.sound_timer = self.v[vx],
-            (0xF, vx, 0x1, 0xE) => self.i += u16::from(self.v[vx]),
-            (0xF, n, 0x2, 0x9) => self.i = n as u16 * 5,
-            (0xF, vx, 0x3, 0x3) => {
-                let v = self.v[vx];
-                self.ram[self.i as usize] = v / 100;
-                self.ram[(self.i + 1) as usize] = (v / 10) % 10;
-                self.ram[(self.i + 2) as usize] = v % 10;
-            }
-            (0xF, vx, 0x5, 0x5) => {
-                self.ram[self.i as usize..][0..=vx].copy_from_slice(&self.v[0..=vx])
-            }
+fn read_rom(path: &Path) -> Vec<u8> {
+    match fs::read(path) {
+        Ok(data) => data,
+        Err(err) => {
+            eprintln!("Could not read {}: {}", path.display(), err);
+            process::exit(1);
+        }
+    }
+}
 
-            (0xF, vx, 0x6, 0x5) => {
-                self.v[0..=vx].copy_from_slice(&self.ram[self.i as usize..][0..=vx])
+fn disasm(args: &[String]) {
+    let mut syntax = Syntax::Cowgod;
+    let mut rom = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--syntax" => {
+                let name = args
+                    .next()
+                    .unwrap_or_else(|| usage_error("--syntax needs a name"));
+                syntax = Syntax::from_name(name)
+                    .unwrap_or_else(|| usage_error(&format!("Unknown syntax {}", name)));
             }
+            flag if flag.starts_with("--") => usage_error(&format!("Unknown option {}", flag)),
+            path if rom.is_none() => rom = Some(PathBuf::from(path)),
+            _ => usage_error("Only one ROM may be given"),
+        }
+    }
+    let rom = rom.unwrap_or_else(|| usage_error("No ROM given"));
+    let rom_data = read_rom(&rom);
+    print!("{}", Disassembly::new(&rom_data).listing(syntax));
+}
 
-            _ => panic!("Invalid op: {:X?}", op),
+fn assemble(args: &[String]) {
+    let mut source = None;
+    let mut output = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" => {
+                let path = args
+                    .next()
+                    .unwrap_or_else(|| usage_error("-o needs a path"));
+                output = Some(PathBuf::from(path));
+            }
+            flag if flag.starts_with('-') => usage_error(&format!("Unknown option {}", flag)),
+            path if source.is_none() => source = Some(PathBuf::from(path)),
+            _ => usage_error("Only one source file may be given"),
         }
     }
+    let source = source.unwrap_or_else(|| usage_error("No source file given"));
+    let output = output.unwrap_or_else(|| source.with_extension("ch8"));
 
-    fn update_timers(&mut self) {
-        if self.delay_timer > 0 {
-            self.delay_timer -= 1;
+    let text = match fs::read_to_string(&source) {
+        Ok(text) => text,
+        Err(err) => {
+            eprintln!("Could not read {}: {}", source.display(), err);
+            process::exit(1);
         }
-        if self.sound_timer > 0 {
-            self.sound_timer -= 1;
+    };
+    let rom = match asm::assemble(&text) {
+        Ok(rom) => rom,
+        Err(err) => {
+            eprintln!("{}:{}", source.display(), err);
+            process::exit(1);
         }
+    };
+    if let Err(err) = fs::write(&output, rom) {
+        eprintln!("Could not write {}: {}", output.display(), err);
+        process::exit(1);
     }
+}
+
+// The terminal beeps by default, headless runs are silent unless asked.
+fn open_audio(options: &Options) -> io::Result<Option<Box<dyn AudioSink>>> {
+    let wave = || {
+        SquareWave::new(
+            options.tone,
+            f64::from(options.volume) / 100.0,
+            SquareWave::DEFAULT_SAMPLE_RATE,
+        )
+    };
+    let sink: Box<dyn AudioSink> = match &options.audio {
+        None if options.headless => return Ok(None),
+        Some(AudioOutput::None) => return Ok(None),
+        None | Some(AudioOutput::Bell) => Box::new(Bell::new(io::stdout())),
+        Some(AudioOutput::Wav(path)) => {
+            Box::new(Wav::new(BufWriter::new(fs::File::create(path)?), wave())?)
+        }
+        Some(AudioOutput::Pcm(path)) => {
+            Box::new(Pcm::new(BufWriter::new(fs::File::create(path)?), wave()))
+        }
+    };
+    Ok(Some(sink))
+}
 
-    fn n2u8(n1: usize, n2: usize) -> u8 {
-        (n1 << 4 | n2) as u8
+fn new_machine(options: &Options, rom: &[u8]) -> Chip8 {
+    let mut chip8 = if options.xo_chip {
+        Chip8::new_xo_chip()
+    } else {
+        Chip8::new()
+    };
+    if let Some(quirks) = options.quirks {
+        chip8.set_quirks(quirks);
     }
-    fn n3u16(n1: usize, n2: usize, n3: usize) -> u16 {
-        (n1 << 8 | n2 << 4 | n3) as u16
+    if let Some(seed) = options.seed {
+        chip8.set_seed(seed);
     }
+    if let Err(err) = chip8.load_rom(rom) {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+    chip8
 }
 
-impl Display for Cpu {
-    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
-        writeln!(
-            f,
-            "PC: {}\nI: {}\nV: {:?}\nDelay timer: {}\nSound timer: {}\nStack: {:?}\nSp: {}",
-            self.pc, self.i, self.v, self.delay_timer, self.sound_timer, self.stack, self.sp
-        )
+fn run(args: &[String]) {
+    let options = Options::parse(args).unwrap_or_else(|err| usage_error(&err));
+    let rom_data = read_rom(&options.rom);
+    let state_path = options.rom.with_extension("state");
+    let movie = options.replay.as_ref().map(|path| {
+        let movie = fs::read(path)
+            .map_err(|err| err.to_string())
+            .and_then(|data| Movie::from_bytes(&data).map_err(|err| err.to_string()));
+        movie.unwrap_or_else(|err| {
+            eprintln!("Could not read movie {}: {}", path.display(), err);
+            process::exit(1);
+        })
+    });
+    // A movie brings its own machine, seed and speed.
+    let (mut chip8, speed) = match &movie {
+        Some(movie) => match movie.machine(&rom_data) {
+            Ok(chip8) => (chip8, movie.speed()),
+            Err(err) => {
+                eprintln!("Could not play movie: {}", err);
+                process::exit(1);
+            }
+        },
+        None => (new_machine(&options, &rom_data), options.speed),
+    };
+    let mut audio = open_audio(&options).unwrap_or_else(|err| {
+        eprintln!("Could not open audio output: {}", err);
+        process::exit(1);
+    });
+    // Without --record the terminal's record key writes next to the ROM.
+    let record_path = options
+        .record
+        .clone()
+        .unwrap_or_else(|| options.rom.with_extension("gif"));
+    let mut recording = Recording::new(
+        record_path.clone(),
+        CaptureFormat::from_path(&record_path).unwrap_or(CaptureFormat::Gif),
+        options.scale,
+        options
+            .theme
+            .map_or(DEFAULT_PALETTE, |theme| theme.palette()),
+    );
+    if options.record.is_some() {
+        if let Err(err) = recording.toggle() {
+            eprintln!("Could not record to {}: {}", record_path.display(), err);
+            process::exit(1);
+        }
     }
-}
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        eprintln!("Usage: rustichip8 rom.ch8");
+    if options.headless {
+        // The screen is still dumped after a fault, it shows how far the
+        // program got.
+        let (mut audio_result, mut record_result) = (Ok(()), Ok(()));
+        let mut after_frame = |chip8: &Chip8| {
+            if let (Some(sink), Ok(())) = (&mut audio, &audio_result) {
+                audio_result = sink.frame(Tone::of(chip8));
+            }
+            if record_result.is_ok() {
+                record_result = recording.frame(chip8);
+            }
+        };
+        let result = match &movie {
+            Some(movie) => headless::play_with(&mut chip8, movie, after_frame),
+            None => headless::run_with(&mut chip8, speed, options.cycles, &mut after_frame),
+        };
+        if let Some(sink) = &mut audio {
+            audio_result = audio_result.and_then(|()| sink.finish());
+        }
+        if let Err(err) = audio_result {
+            eprintln!("Could not write audio: {}", err);
+            process::exit(1);
+        }
+        if let Err(err) = record_result.and_then(|()| recording.finish()) {
+            eprintln!("Could not record to {}: {}", record_path.display(), err);
+            process::exit(1);
+        }
+        let image = dump::dump(&chip8, options.dump);
+        let written = match &options.output {
+            Some(path) => fs::write(path, image),
+            None => io::stdout().write_all(&image),
+        };
+        if let Err(err) = written {
+            eprintln!("Could not write screen dump: {}", err);
+            process::exit(1);
+        }
+        if let Err(err) = result {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
         return;
     }
 
-    let rom = Path::new(args[1].as_str());
-    let rom_data = fs::read(rom).unwrap();
-    let mut cpu = Cpu::new();
-    cpu.load_rom(rom_data.as_slice());
-    cpu.run();
+    // Either a theme or a colour depth turns colours on.
+    let colors = match (options.theme, options.color_depth) {
+        (Some(theme), depth) => Some((theme, depth.unwrap_or_else(ColorDepth::detect))),
+        (None, Some(depth)) => Some((Theme::OCTO, depth)),
+        (None, None) => None,
+    };
+    let mut input = match (movie, &options.record_input) {
+        (Some(movie), _) => Input::Play(movie),
+        (None, Some(_)) => Input::Record(Movie::new(&chip8, &rom_data, speed)),
+        (None, None) => Input::Keyboard,
+    };
+    let result = terminal::run(
+        &mut chip8,
+        &rom_data,
+        speed,
+        &state_path,
+        options.debug,
+        Screen::new(
+            options.render,
+            options.double_width,
+            colors,
+            options.persistence,
+        ),
+        audio.as_mut().map(|sink| &mut **sink as &mut dyn AudioSink),
+        &mut input,
+        &mut recording,
+    );
+    // Recordings are kept even after a fault, to show how it came about.
+    if let (Input::Record(movie), Some(path)) = (&input, &options.record_input) {
+        if let Err(err) = fs::write(path, movie.to_bytes()) {
+            eprintln!("Could not write movie {}: {}", path.display(), err);
+        }
+    }
+    if let Some(sink) = &mut audio {
+        if let Err(err) = sink.finish() {
+            eprintln!("Could not write audio: {}", err);
+        }
+    }
+    if let Err(err) = recording.finish() {
+        eprintln!("Could not record to {}: {}", record_path.display(), err);
+    }
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
 }
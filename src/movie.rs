@@ -0,0 +1,262 @@
+//! Input movies: the keypad presses and releases of a run, frame by frame.
+//!
+//! A movie file starts with a four byte magic and a format version, then the
+//! ROM hash, random seed, machine mode, quirks, speed and length in frames,
+//! followed by every key event as its frame number and a byte holding the
+//! key, with the top bit set for a press. Integers are big-endian.
+
+use crate::chip8::state::{Reader, Writer};
+use crate::chip8::Chip8;
+use crate::error::MovieError;
+use crate::quirks::Quirks;
+use crate::scheduler::Speed;
+
+const MAGIC: &[u8; 4] = b"RC8M";
+const VERSION: u8 = 1;
+const PRESSED: u8 = 0x80;
+
+/// A recorded run. Besides the input it holds everything else that decides
+/// what the program does, so playing it back on a machine from `machine`
+/// repeats the run frame for frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    rom_hash: u64,
+    seed: u64,
+    xo_chip: bool,
+    quirks: Quirks,
+    speed: Speed,
+    frames: u64,
+    events: Vec<Event>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Event {
+    frame: u64,
+    key: u8,
+    pressed: bool,
+}
+
+impl Movie {
+    /// Starts recording a run of `rom` at `speed` on `chip8`, which should
+    /// have just been created with the ROM loaded.
+    pub fn new(chip8: &Chip8, rom: &[u8], speed: Speed) -> Self {
+        Movie {
+            rom_hash: rom_hash(rom),
+            seed: chip8.seed(),
+            xo_chip: chip8.is_xo_chip(),
+            quirks: chip8.quirks(),
+            speed,
+            frames: 0,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn speed(&self) -> Speed {
+        self.speed
+    }
+
+    /// Length of the movie in frames.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Presses or releases `key` on `chip8`, recording it against the
+    /// current frame if it changes anything.
+    pub fn set_key(&mut self, chip8: &mut Chip8, key: u8, pressed: bool) {
+        if chip8.is_key_down(key) != pressed {
+            chip8.set_key(key, pressed);
+            self.events.push(Event {
+                frame: self.frames,
+                key,
+                pressed,
+            });
+        }
+    }
+
+    /// Moves recording on to the next frame, after the current one has run.
+    pub fn end_frame(&mut self) {
+        self.frames += 1;
+    }
+
+    /// A new machine with `rom` loaded and the mode, quirks and seed the
+    /// movie was recorded with, ready to play it back from the first frame.
+    pub fn machine(&self, rom: &[u8]) -> Result<Chip8, MovieError> {
+        if rom_hash(rom) != self.rom_hash {
+            return Err(MovieError::WrongRom);
+        }
+        let mut chip8 = if self.xo_chip {
+            Chip8::new_xo_chip()
+        } else {
+            Chip8::new()
+        };
+        chip8.set_quirks(self.quirks);
+        chip8.set_seed(self.seed);
+        chip8.load_rom(rom).map_err(|_| MovieError::WrongRom)?;
+        Ok(chip8)
+    }
+
+    /// Presses and releases the keys recorded for `frame`, which should be
+    /// done just before running it.
+    pub fn play_frame(&self, chip8: &mut Chip8, frame: u64) {
+        let start = self.events.partition_point(|event| event.frame < frame);
+        for event in self.events[start..]
+            .iter()
+            .take_while(|event| event.frame == frame)
+        {
+            chip8.set_key(event.key, event.pressed);
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = Writer(Vec::with_capacity(64 + self.events.len() * 9));
+        w.bytes(MAGIC);
+        w.u8(VERSION);
+        w.u64(self.rom_hash);
+        w.u64(self.seed);
+        w.bool(self.xo_chip);
+        w.quirks(self.quirks);
+        match self.speed {
+            Speed::InstructionsPerFrame(ipf) => {
+                w.u8(0);
+                w.bytes(&ipf.to_be_bytes());
+            }
+            Speed::Hertz(hz) => {
+                w.u8(1);
+                w.bytes(&hz.to_be_bytes());
+            }
+        }
+        w.u64(self.frames);
+        for event in &self.events {
+            w.u64(event.frame);
+            w.u8(event.key | if event.pressed { PRESSED } else { 0 });
+        }
+        w.0
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Movie, MovieError> {
+        let mut r = Reader(data);
+        if r.bytes(MAGIC.len())? != MAGIC {
+            return Err(MovieError::BadMagic);
+        }
+        let version = r.u8()?;
+        if version != VERSION {
+            return Err(MovieError::UnsupportedVersion(version));
+        }
+
+        let rom_hash = r.u64()?;
+        let seed = r.u64()?;
+        let xo_chip = r.bool()?;
+        let quirks = r.quirks()?;
+        let kind = r.u8()?;
+        let mut value = [0; 4];
+        value.copy_from_slice(r.bytes(4)?);
+        let speed = match kind {
+            0 => Speed::InstructionsPerFrame(u32::from_be_bytes(value)),
+            1 => Speed::Hertz(u32::from_be_bytes(value)),
+            _ => return Err(MovieError::Corrupt),
+        };
+        if !speed.is_valid() {
+            return Err(MovieError::Corrupt);
+        }
+        let frames = r.u64()?;
+        let mut events = Vec::new();
+        while !r.0.is_empty() {
+            let frame = r.u64()?;
+            let key = r.u8()?;
+            let last = events.last().map_or(0, |event: &Event| event.frame);
+            if frame < last || frame > frames || key & !PRESSED >= 16 {
+                return Err(MovieError::Corrupt);
+            }
+            events.push(Event {
+                frame,
+                key: key & !PRESSED,
+                pressed: key & PRESSED != 0,
+            });
+        }
+        Ok(Movie {
+            rom_hash,
+            seed,
+            xo_chip,
+            quirks,
+            speed,
+            frames,
+            events,
+        })
+    }
+}
+
+// 64-bit FNV-1a.
+fn rom_hash(rom: &[u8]) -> u64 {
+    rom.iter().fold(0xCBF2_9CE4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01B3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROM: [u8; 2] = [0x12, 0x00];
+
+    fn recording() -> Movie {
+        let mut chip8 = Chip8::new_xo_chip();
+        chip8.set_seed(42);
+        chip8.load_rom(&ROM).unwrap();
+        let mut movie = Movie::new(&chip8, &ROM, Speed::Hertz(500));
+        movie.set_key(&mut chip8, 0x5, true);
+        movie.set_key(&mut chip8, 0x5, true);
+        movie.end_frame();
+        movie.end_frame();
+        movie.set_key(&mut chip8, 0x5, false);
+        movie.set_key(&mut chip8, 0xF, true);
+        movie.end_frame();
+        movie
+    }
+
+    #[test]
+    fn round_trips() {
+        let movie = recording();
+        assert_eq!(movie.events.len(), 3);
+        assert_eq!(movie.frames(), 3);
+        let bytes = movie.to_bytes();
+        assert_eq!(Movie::from_bytes(&bytes), Ok(movie));
+        assert_eq!(
+            Movie::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(MovieError::Truncated)
+        );
+        assert_eq!(Movie::from_bytes(b"RC8S\x01"), Err(MovieError::BadMagic));
+    }
+
+    #[test]
+    fn rejects_bad_speeds() {
+        let bytes = recording().to_bytes();
+        // The speed is followed by the frame count and three 9-byte events.
+        let speed = bytes.len() - 4 - 8 - 3 * 9;
+        assert_eq!(bytes[speed..speed + 4], 500u32.to_be_bytes());
+        for &hz in &[0, u32::MAX] {
+            let mut bad = bytes.clone();
+            bad[speed..speed + 4].copy_from_slice(&hz.to_be_bytes());
+            assert_eq!(Movie::from_bytes(&bad), Err(MovieError::Corrupt));
+        }
+    }
+
+    #[test]
+    fn plays_back_on_recorded_machine() {
+        let movie = recording();
+        assert_eq!(
+            movie.machine(&[0x00, 0xE0]).err(),
+            Some(MovieError::WrongRom)
+        );
+
+        let mut chip8 = movie.machine(&ROM).unwrap();
+        assert!(chip8.is_xo_chip());
+        assert_eq!(chip8.seed(), 42);
+        movie.play_frame(&mut chip8, 0);
+        assert!(chip8.is_key_down(0x5));
+        movie.play_frame(&mut chip8, 1);
+        assert!(chip8.is_key_down(0x5));
+        movie.play_frame(&mut chip8, 2);
+        assert!(!chip8.is_key_down(0x5));
+        assert!(chip8.is_key_down(0xF));
+    }
+}
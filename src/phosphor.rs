@@ -0,0 +1,122 @@
+/// Hides the flicker of sprites that are erased and redrawn every frame by
+/// keeping recently cleared pixels visible for a little longer, like the
+/// slow phosphor of an old CRT.
+pub struct Phosphor {
+    frames: u8,
+    fade: bool,
+    levels: Vec<u8>,
+    planes: Vec<u8>,
+    previous: Vec<u8>,
+}
+
+impl Phosphor {
+    /// With `fade` a cleared pixel dims over `frames` frames, which needs a
+    /// display that can show the shades in between. Otherwise each frame is
+    /// shown ORed with the one before it.
+    pub fn new(frames: u8, fade: bool) -> Self {
+        Phosphor {
+            frames: frames.max(1),
+            fade,
+            levels: Vec::new(),
+            planes: Vec::new(),
+            previous: Vec::new(),
+        }
+    }
+
+    /// Takes the next frame and appends every pixel as it should be shown to
+    /// `out`: its lit planes and its brightness from 0 to 255. Returns true
+    /// if the output will keep changing even if the frame doesn't.
+    pub fn update(&mut self, framebuffer: &[u8], out: &mut Vec<(u8, u8)>) -> bool {
+        if self.levels.len() != framebuffer.len() {
+            self.levels = vec![0; framebuffer.len()];
+            self.planes = vec![0; framebuffer.len()];
+            self.previous = vec![0; framebuffer.len()];
+        }
+
+        if !self.fade {
+            let changed = self.previous[..] != framebuffer[..];
+            for (&pix, &previous) in framebuffer.iter().zip(&self.previous) {
+                let planes = (pix | previous) & 0x3;
+                out.push((planes, if planes != 0 { 0xFF } else { 0 }));
+            }
+            self.previous.copy_from_slice(framebuffer);
+            return changed;
+        }
+
+        let mut fading = false;
+        let pixels = self.levels.iter_mut().zip(self.planes.iter_mut());
+        for (&pix, (level, planes)) in framebuffer.iter().zip(pixels) {
+            if pix & 0x3 != 0 {
+                *level = self.frames;
+                *planes = pix & 0x3;
+            } else if *level > 0 {
+                *level -= 1;
+            }
+            if *level == 0 {
+                *planes = 0;
+            }
+            fading |= *level > 0 && *level < self.frames;
+            let brightness = (u16::from(*level) * 0xFF / u16::from(self.frames)) as u8;
+            out.push((*planes, brightness));
+        }
+        fading
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(phosphor: &mut Phosphor, framebuffer: &[u8]) -> (Vec<(u8, u8)>, bool) {
+        let mut out = Vec::new();
+        let fading = phosphor.update(framebuffer, &mut out);
+        (out, fading)
+    }
+
+    #[test]
+    fn fades_over_frames() {
+        let mut phosphor = Phosphor::new(3, true);
+        assert_eq!(
+            update(&mut phosphor, &[2, 0]),
+            (vec![(2, 0xFF), (0, 0)], false)
+        );
+        assert_eq!(
+            update(&mut phosphor, &[0, 1]),
+            (vec![(2, 0xAA), (1, 0xFF)], true)
+        );
+        assert_eq!(
+            update(&mut phosphor, &[0, 1]),
+            (vec![(2, 0x55), (1, 0xFF)], true)
+        );
+        assert_eq!(
+            update(&mut phosphor, &[0, 1]),
+            (vec![(0, 0), (1, 0xFF)], false)
+        );
+    }
+
+    #[test]
+    fn relit_pixels_are_bright_again() {
+        let mut phosphor = Phosphor::new(4, true);
+        update(&mut phosphor, &[1]);
+        update(&mut phosphor, &[0]);
+        assert_eq!(update(&mut phosphor, &[0]), (vec![(1, 0x7F)], true));
+        assert_eq!(update(&mut phosphor, &[3]), (vec![(3, 0xFF)], false));
+    }
+
+    #[test]
+    fn ors_with_previous_frame_without_fading() {
+        let mut phosphor = Phosphor::new(5, false);
+        assert_eq!(
+            update(&mut phosphor, &[1, 0]),
+            (vec![(1, 0xFF), (0, 0)], true)
+        );
+        assert_eq!(
+            update(&mut phosphor, &[0, 2]),
+            (vec![(1, 0xFF), (2, 0xFF)], true)
+        );
+        assert_eq!(
+            update(&mut phosphor, &[0, 2]),
+            (vec![(0, 0), (2, 0xFF)], false)
+        );
+    }
+}
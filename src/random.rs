@@ -0,0 +1,35 @@
+/// The random number generator behind CXNN. It is SplitMix64, whose whole
+/// state is one word, so it fits in save states and rewind snapshots and a
+/// replay from either draws the same numbers as the original run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Random {
+    seed: u64,
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        Random { seed, state: seed }
+    }
+
+    /// Picks up a sequence part way through, as saved by `seed` and `state`.
+    pub fn restore(seed: u64, state: u64) -> Self {
+        Random { seed, state }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    pub fn next_u8(&mut self) -> u8 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        (z ^ (z >> 31)) as u8
+    }
+}
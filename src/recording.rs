@@ -0,0 +1,100 @@
+use rustichip8::capture::{FrameSink, Gif, Palette, PpmSequence, Y4m};
+use rustichip8::Chip8;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+
+/// File format of a screen recording, picked by its extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureFormat {
+    Gif,
+    Y4m,
+    Ppm,
+}
+
+impl CaptureFormat {
+    pub fn from_path(path: &Path) -> Option<CaptureFormat> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "gif" => Some(CaptureFormat::Gif),
+            "y4m" => Some(CaptureFormat::Y4m),
+            "ppm" => Some(CaptureFormat::Ppm),
+            _ => None,
+        }
+    }
+}
+
+/// A screen recording that can be switched on and off while running. The
+/// file is only created once recording is first switched on, and switching
+/// it back on later carries on in the same file.
+pub struct Recording {
+    path: PathBuf,
+    format: CaptureFormat,
+    scale: u16,
+    palette: Palette,
+    sink: Option<Box<dyn FrameSink>>,
+    on: bool,
+}
+
+impl Recording {
+    pub fn new(path: PathBuf, format: CaptureFormat, scale: u16, palette: Palette) -> Self {
+        Recording {
+            path,
+            format,
+            scale,
+            palette,
+            sink: None,
+            on: false,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.on
+    }
+
+    pub fn toggle(&mut self) -> io::Result<()> {
+        if self.sink.is_none() {
+            self.sink = Some(self.open()?);
+        }
+        self.on = !self.on;
+        Ok(())
+    }
+
+    fn open(&self) -> io::Result<Box<dyn FrameSink>> {
+        Ok(match self.format {
+            CaptureFormat::Gif => {
+                let out = BufWriter::new(File::create(&self.path)?);
+                Box::new(Gif::new(out, self.scale, self.palette)?)
+            }
+            CaptureFormat::Y4m => {
+                let out = BufWriter::new(File::create(&self.path)?);
+                Box::new(Y4m::new(out, self.scale, self.palette)?)
+            }
+            CaptureFormat::Ppm => Box::new(PpmSequence::new(&self.path, self.scale, self.palette)),
+        })
+    }
+
+    /// Captures the screen if recording is on. Recording is switched off if
+    /// it fails.
+    pub fn frame(&mut self, chip8: &Chip8) -> io::Result<()> {
+        let result = match &mut self.sink {
+            Some(sink) if self.on => sink.frame(chip8),
+            _ => return Ok(()),
+        };
+        self.on = result.is_ok();
+        result
+    }
+
+    /// Finishes the file, if one was started.
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.on = false;
+        match self.sink.take() {
+            Some(mut sink) => sink.finish(),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,248 @@
+use crate::chip8::Chip8;
+use crate::error::Chip8Error;
+use crate::scheduler::Scheduler;
+use std::collections::VecDeque;
+use std::iter;
+
+/// A ring buffer of machine snapshots for running a program backwards.
+///
+/// Front ends take a snapshot before every frame, and before every
+/// instruction stepped by hand. Going back a single instruction restores the
+/// newest snapshot from before it and replays the instructions in between.
+/// Only the newest snapshot is kept in full, the others are stored as their
+/// difference from the next one, which is rarely more than a few bytes.
+pub struct Rewind {
+    capacity: usize,
+    // The newest snapshot and the machine's cycle count when it was taken.
+    newest: Option<(Vec<u8>, u64)>,
+    // Older snapshots, oldest first, each diffed against the one after it.
+    older: VecDeque<(Vec<u8>, u64)>,
+}
+
+impl Rewind {
+    /// Thirty seconds of frames.
+    pub const DEFAULT_CAPACITY: usize = 30 * Scheduler::FRAME_RATE as usize;
+
+    /// Keeps up to `capacity` snapshots, dropping the oldest when full.
+    pub fn new(capacity: usize) -> Self {
+        Rewind {
+            capacity: capacity.max(1),
+            newest: None,
+            older: VecDeque::new(),
+        }
+    }
+
+    /// Number of snapshots held.
+    pub fn len(&self) -> usize {
+        self.older.len() + self.newest.is_some() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.newest.is_none()
+    }
+
+    /// Forgets every snapshot, for when the machine is replaced by one
+    /// with a different history.
+    pub fn clear(&mut self) {
+        self.newest = None;
+        self.older.clear();
+    }
+
+    /// Records the machine as it is now. If no instruction has been fetched
+    /// since the newest snapshot, that one is replaced instead.
+    pub fn snapshot(&mut self, chip8: &Chip8) {
+        let state = chip8.save_state();
+        let cycles = chip8.cycles();
+        if let Some((newest, newest_cycles)) = self.newest.take() {
+            if newest_cycles != cycles {
+                self.older.push_back((diff(&newest, &state), newest_cycles));
+                while self.older.len() >= self.capacity {
+                    self.older.pop_front();
+                }
+            }
+        }
+        self.newest = Some((state, cycles));
+    }
+
+    /// Goes back to the newest snapshot, or to the one before it if no
+    /// instruction has been fetched since. Snapshots after the restored one
+    /// are dropped. Returns false if there is nothing to go back to.
+    pub fn back_frame(&mut self, chip8: &mut Chip8) -> bool {
+        if self
+            .newest
+            .as_ref()
+            .is_some_and(|&(_, cycles)| cycles >= chip8.cycles())
+        {
+            self.pop();
+        }
+        match &self.newest {
+            Some((state, _)) => {
+                restore(chip8, state);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Goes back to just before the last instruction fetched, by replaying
+    /// from the newest snapshot taken before it. Returns false if there are
+    /// no snapshots that far back. Instructions are replayed with the keys
+    /// held when the snapshot was taken, so front ends must take a snapshot
+    /// whenever keys change.
+    pub fn back_step(&mut self, chip8: &mut Chip8) -> Result<bool, Chip8Error> {
+        let target = match chip8.cycles().checked_sub(1) {
+            Some(target) => target,
+            None => return Ok(false),
+        };
+        while self
+            .newest
+            .as_ref()
+            .is_some_and(|&(_, cycles)| cycles > target)
+        {
+            self.pop();
+        }
+        let state = match &self.newest {
+            Some((state, _)) => state,
+            None => return Ok(false),
+        };
+        restore(chip8, state);
+        while chip8.cycles() < target {
+            let cycles = chip8.cycles();
+            chip8.step()?;
+            // Halted or waiting for a key, which the replay can't get past.
+            if chip8.cycles() == cycles {
+                break;
+            }
+        }
+        Ok(true)
+    }
+
+    // Drops the newest snapshot, making the one before it the newest.
+    fn pop(&mut self) {
+        self.newest = match (self.newest.take(), self.older.pop_back()) {
+            (Some((newest, _)), Some((delta, cycles))) => Some((undiff(&newest, &delta), cycles)),
+            _ => None,
+        };
+    }
+}
+
+fn restore(chip8: &mut Chip8, state: &[u8]) {
+    chip8
+        .load_state(state)
+        .expect("snapshots are valid save states");
+}
+
+// Encodes `old` against `new`: the length of `old`, then each run of bytes
+// that differ as the number of equal bytes before it, its length and the
+// bytes XORed together. Bytes past the end of `new` count as zero.
+fn diff(old: &[u8], new: &[u8]) -> Vec<u8> {
+    let xor = |at: usize| old[at] ^ new.get(at).copied().unwrap_or(0);
+    let mut out = (old.len() as u32).to_be_bytes().to_vec();
+    let (mut at, mut last) = (0, 0);
+    while at < old.len() {
+        if xor(at) == 0 {
+            at += 1;
+            continue;
+        }
+        let start = at;
+        while at < old.len() && xor(at) != 0 {
+            at += 1;
+        }
+        out.extend_from_slice(&((start - last) as u32).to_be_bytes());
+        out.extend_from_slice(&((at - start) as u32).to_be_bytes());
+        out.extend((start..at).map(xor));
+        last = at;
+    }
+    out
+}
+
+// Rebuilds `old` from `new` and the output of `diff`.
+fn undiff(new: &[u8], delta: &[u8]) -> Vec<u8> {
+    let word = |at: usize| {
+        u32::from_be_bytes([delta[at], delta[at + 1], delta[at + 2], delta[at + 3]]) as usize
+    };
+    let mut old: Vec<u8> = new
+        .iter()
+        .copied()
+        .chain(iter::repeat(0))
+        .take(word(0))
+        .collect();
+    let (mut at, mut pos) = (4, 0);
+    while at < delta.len() {
+        pos += word(at);
+        let len = word(at + 4);
+        at += 8;
+        for (byte, x) in old[pos..pos + len].iter_mut().zip(&delta[at..at + len]) {
+            *byte ^= x;
+        }
+        pos += len;
+        at += len;
+    }
+    old
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn machine(rom: &[u8]) -> Chip8 {
+        let mut chip8 = Chip8::new();
+        chip8.load_rom(rom).unwrap();
+        chip8
+    }
+
+    #[test]
+    fn diff_round_trips() {
+        let old = [1, 2, 3, 4, 5, 6, 7];
+        for new in [
+            &[1, 2, 3, 4, 5, 6, 7][..],
+            &[1, 9, 3, 4, 0, 0, 7],
+            &[1, 2],
+            &[0; 10],
+        ] {
+            assert_eq!(undiff(new, &diff(&old, new)), old);
+        }
+        assert_eq!(diff(&old, &old).len(), 4);
+    }
+
+    #[test]
+    fn back_frame_restores_snapshots() {
+        // 7001: V0 += 1, then jump back to it.
+        let mut chip8 = machine(&[0x70, 0x01, 0x12, 0x00]);
+        let mut rewind = Rewind::new(3);
+        for _ in 0..5 {
+            rewind.snapshot(&chip8);
+            chip8.run_frame(2).unwrap();
+        }
+        assert_eq!(chip8.v()[0], 5);
+        assert_eq!(rewind.len(), 3);
+
+        assert!(rewind.back_frame(&mut chip8));
+        assert_eq!(chip8.v()[0], 4);
+        assert!(rewind.back_frame(&mut chip8));
+        assert!(rewind.back_frame(&mut chip8));
+        assert_eq!(chip8.v()[0], 2);
+        assert!(!rewind.back_frame(&mut chip8));
+        assert!(rewind.is_empty());
+    }
+
+    #[test]
+    fn back_step_replays_from_snapshot() {
+        let mut chip8 = machine(&[0x70, 0x01, 0x12, 0x00]);
+        let mut rewind = Rewind::new(10);
+        rewind.snapshot(&chip8);
+        chip8.run_frame(5).unwrap();
+        assert_eq!((chip8.v()[0], chip8.pc()), (3, 0x202));
+
+        assert!(rewind.back_step(&mut chip8).unwrap());
+        assert_eq!((chip8.v()[0], chip8.pc()), (2, 0x200));
+        assert_eq!(chip8.cycles(), 4);
+        assert!(rewind.back_step(&mut chip8).unwrap());
+        assert_eq!((chip8.v()[0], chip8.pc()), (2, 0x202));
+        for _ in 0..3 {
+            assert!(rewind.back_step(&mut chip8).unwrap());
+        }
+        assert_eq!((chip8.v()[0], chip8.pc(), chip8.cycles()), (0, 0x200, 0));
+        assert!(!rewind.back_step(&mut chip8).unwrap());
+    }
+}
@@ -0,0 +1,173 @@
+use std::fmt::{Display, Error, Formatter};
+use std::time::Duration;
+
+/// How fast the CPU runs relative to the 60 Hz timers and display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Speed {
+    /// A fixed number of instructions every frame.
+    InstructionsPerFrame(u32),
+    /// Instructions per second, spread as evenly as possible over the frames.
+    Hertz(u32),
+}
+
+impl Default for Speed {
+    fn default() -> Self {
+        Speed::InstructionsPerFrame(10)
+    }
+}
+
+impl Speed {
+    /// The fastest speed front ends accept. Much beyond this a frame takes
+    /// longer than 1/60 of a second to emulate.
+    pub const MAX_INSTRUCTIONS_PER_FRAME: u32 = 100_000;
+
+    /// Whether the speed runs at least one instruction a second and no more
+    /// than `MAX_INSTRUCTIONS_PER_FRAME`.
+    pub fn is_valid(self) -> bool {
+        match self {
+            Speed::InstructionsPerFrame(ipf) => {
+                (1..=Speed::MAX_INSTRUCTIONS_PER_FRAME).contains(&ipf)
+            }
+            Speed::Hertz(hz) => {
+                (1..=Speed::MAX_INSTRUCTIONS_PER_FRAME * Scheduler::FRAME_RATE).contains(&hz)
+            }
+        }
+    }
+
+    /// Twice the speed, but never more than `MAX_INSTRUCTIONS_PER_FRAME`.
+    pub fn doubled(self) -> Speed {
+        let max = Speed::MAX_INSTRUCTIONS_PER_FRAME;
+        match self {
+            Speed::InstructionsPerFrame(ipf) => {
+                Speed::InstructionsPerFrame(ipf.saturating_mul(2).min(max))
+            }
+            Speed::Hertz(hz) => Speed::Hertz(hz.saturating_mul(2).min(max * Scheduler::FRAME_RATE)),
+        }
+    }
+
+    /// Half the speed, but never less than one instruction per frame unless it
+    /// already was.
+    pub fn halved(self) -> Speed {
+        match self {
+            Speed::InstructionsPerFrame(ipf) => Speed::InstructionsPerFrame((ipf / 2).max(1)),
+            Speed::Hertz(hz) => Speed::Hertz((hz / 2).max(hz.min(Scheduler::FRAME_RATE))),
+        }
+    }
+}
+
+impl Display for Speed {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        match self {
+            Speed::InstructionsPerFrame(ipf) => write!(f, "{} instructions per frame", ipf),
+            Speed::Hertz(hz) => write!(f, "{} Hz", hz),
+        }
+    }
+}
+
+/// Splits execution into 60 Hz frames, each running some instructions and
+/// then a single timer tick.
+pub struct Scheduler {
+    speed: Speed,
+    remainder: u64,
+}
+
+impl Scheduler {
+    pub const FRAME_RATE: u32 = 60;
+
+    pub fn new(speed: Speed) -> Self {
+        Scheduler {
+            speed,
+            remainder: 0,
+        }
+    }
+
+    pub fn speed(&self) -> Speed {
+        self.speed
+    }
+
+    pub fn set_speed(&mut self, speed: Speed) {
+        self.speed = speed;
+        self.remainder = 0;
+    }
+
+    /// Wall-clock length of one frame.
+    pub fn frame_duration() -> Duration {
+        Duration::from_secs(1) / Scheduler::FRAME_RATE
+    }
+
+    /// Number of instructions to execute in the next frame.
+    pub fn instructions_for_frame(&mut self) -> u32 {
+        match self.speed {
+            Speed::InstructionsPerFrame(ipf) => ipf,
+            Speed::Hertz(hz) => {
+                let frame_rate = u64::from(Scheduler::FRAME_RATE);
+                self.remainder += u64::from(hz);
+                let instructions = self.remainder / frame_rate;
+                self.remainder %= frame_rate;
+                instructions as u32
+            }
+        }
+    }
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Scheduler::new(Speed::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spreads_hertz_over_frames() {
+        let mut scheduler = Scheduler::new(Speed::Hertz(90));
+        let frames: Vec<u32> = (0..4).map(|_| scheduler.instructions_for_frame()).collect();
+        assert_eq!(frames, [1, 2, 1, 2]);
+
+        scheduler.set_speed(Speed::Hertz(u32::MAX));
+        assert_eq!(scheduler.instructions_for_frame(), u32::MAX / 60);
+    }
+
+    #[test]
+    fn limits_speed() {
+        assert!(Speed::default().is_valid());
+        assert!(Speed::Hertz(1).is_valid());
+        assert!(!Speed::InstructionsPerFrame(0).is_valid());
+        assert!(!Speed::Hertz(0).is_valid());
+        assert!(!Speed::InstructionsPerFrame(Speed::MAX_INSTRUCTIONS_PER_FRAME + 1).is_valid());
+
+        let mut speed = Speed::Hertz(1000);
+        for _ in 0..32 {
+            speed = speed.doubled();
+        }
+        assert_eq!(speed, Speed::Hertz(6_000_000));
+        assert_eq!(
+            Speed::InstructionsPerFrame(70_000).doubled(),
+            Speed::InstructionsPerFrame(100_000)
+        );
+    }
+
+    #[test]
+    fn halving_never_speeds_up() {
+        let per_second = |speed| match speed {
+            Speed::InstructionsPerFrame(ipf) => ipf * Scheduler::FRAME_RATE,
+            Speed::Hertz(hz) => hz,
+        };
+        for &speed in &[
+            Speed::InstructionsPerFrame(1),
+            Speed::InstructionsPerFrame(2),
+            Speed::Hertz(1),
+            Speed::Hertz(30),
+            Speed::Hertz(60),
+            Speed::Hertz(100),
+        ] {
+            assert!(per_second(speed.halved()) <= per_second(speed), "{}", speed);
+            assert!(speed.halved().is_valid());
+        }
+        assert_eq!(Speed::Hertz(30).halved(), Speed::Hertz(30));
+        assert_eq!(Speed::Hertz(100).halved(), Speed::Hertz(60));
+        assert_eq!(Speed::Hertz(500).halved(), Speed::Hertz(250));
+    }
+}
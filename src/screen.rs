@@ -0,0 +1,354 @@
+use crate::phosphor::Phosphor;
+use crate::theme::{self, ColorDepth, Theme};
+use rustichip8::Chip8;
+use std::fmt::Write;
+use termion::color::{self, Rgb};
+use termion::{clear, cursor};
+
+// Glyph for each combination of lit XO-CHIP bitplanes.
+const PIXELS: [char; 4] = [' ', '█', '░', '▓'];
+// Glyph for each combination of lit top and bottom pixels.
+const HALF_BLOCKS: [char; 4] = [' ', '▀', '▄', '█'];
+// Bit of a braille character for the dot at each (x, y) in its 2x4 cell.
+const BRAILLE_DOTS: [[u32; 4]; 2] = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+const BRAILLE_BLANK: u32 = 0x2800;
+
+/// How machine pixels are packed into terminal cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RenderMode {
+    /// One cell per pixel, with a different shade for each XO-CHIP plane.
+    #[default]
+    Block,
+    /// Two rows of pixels per cell using half block characters.
+    HalfBlock,
+    /// 2x4 pixels per cell using braille patterns.
+    Braille,
+}
+
+impl RenderMode {
+    pub fn from_name(name: &str) -> Option<RenderMode> {
+        match name.to_ascii_lowercase().as_str() {
+            "block" => Some(RenderMode::Block),
+            "half" => Some(RenderMode::HalfBlock),
+            "braille" => Some(RenderMode::Braille),
+            _ => None,
+        }
+    }
+
+    // Pixels covered by one cell.
+    fn cell_size(self) -> (usize, usize) {
+        match self {
+            RenderMode::Block => (1, 1),
+            RenderMode::HalfBlock => (1, 2),
+            RenderMode::Braille => (2, 4),
+        }
+    }
+
+    // Cells needed for a `width` by `height` pixel screen.
+    fn cells(self, width: usize, height: usize) -> (usize, usize) {
+        let (cell_width, cell_height) = self.cell_size();
+        (width.div_ceil(cell_width), height.div_ceil(cell_height))
+    }
+}
+
+// A character and its colours. The colours are ignored without a theme.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Cell {
+    glyph: char,
+    fg: Rgb,
+    bg: Rgb,
+}
+
+const BLANK: Cell = Cell {
+    glyph: ' ',
+    fg: Rgb(0, 0, 0),
+    bg: Rgb(0, 0, 0),
+};
+// Stands for cells in an unknown state, so they are always rewritten.
+const UNKNOWN: Cell = Cell {
+    glyph: '\0',
+    ..BLANK
+};
+
+/// Keeps track of what is on the terminal, so each frame only rewrites the
+/// cells that changed. The machine's screen sits in the top left corner with
+/// a footer of free-form text below it.
+pub struct Screen {
+    mode: RenderMode,
+    double_width: bool,
+    colors: Option<(Theme, ColorDepth)>,
+    phosphor: Option<Phosphor>,
+    // Lit planes and brightness of each pixel as shown.
+    pixels: Vec<(u8, u8)>,
+    fading: bool,
+    rows: Vec<Vec<Cell>>,
+    footer: String,
+}
+
+impl Screen {
+    /// With `double_width` every cell is printed twice side by side, which
+    /// makes up for terminal cells being about twice as tall as they are wide.
+    /// Without `colors` lit pixels are drawn in the terminal's own colours,
+    /// with XO-CHIP planes told apart by shading. A non-zero `persistence`
+    /// keeps cleared pixels on screen for that many frames, fading them out
+    /// on truecolor displays.
+    pub fn new(
+        mode: RenderMode,
+        double_width: bool,
+        colors: Option<(Theme, ColorDepth)>,
+        persistence: u8,
+    ) -> Self {
+        let truecolor = colors.is_some_and(|(_, depth)| depth == ColorDepth::TrueColor);
+        Screen {
+            mode,
+            double_width,
+            colors,
+            phosphor: if persistence > 0 {
+                Some(Phosphor::new(persistence, truecolor))
+            } else {
+                None
+            },
+            pixels: Vec::new(),
+            fading: false,
+            rows: Vec::new(),
+            footer: String::new(),
+        }
+    }
+
+    /// Appends the output that brings the terminal up to date with the
+    /// machine's screen and `footer` to `out`. Nothing is appended if neither
+    /// changed.
+    pub fn draw(&mut self, chip8: &mut Chip8, footer: &str, out: &mut String) {
+        let dirty = chip8.take_dirty();
+        let (cols, height) = self.size(chip8);
+        let cleared = self.rows.len() != height || self.rows[0].len() != cols;
+        if cleared {
+            let _ = write!(out, "{}{}", clear::All, cursor::Hide);
+            self.rows = vec![vec![UNKNOWN; cols]; height];
+        }
+
+        if dirty || cleared || self.fading {
+            self.pixels.clear();
+            match &mut self.phosphor {
+                Some(phosphor) => {
+                    self.fading = phosphor.update(chip8.framebuffer(), &mut self.pixels)
+                }
+                None => self.pixels.extend(chip8.framebuffer().iter().map(|&pix| {
+                    let planes = pix & 0x3;
+                    (planes, if planes != 0 { 0xFF } else { 0 })
+                })),
+            }
+            let cells = self.cells(chip8.width(), chip8.height());
+            let mut current = None;
+            for (y, (row, cells)) in self.rows.iter_mut().zip(cells).enumerate() {
+                // Rewrite each run of changed cells after a single cursor move.
+                let mut x = 0;
+                while x < cols {
+                    if row[x] == cells[x] {
+                        x += 1;
+                        continue;
+                    }
+                    let _ = write!(out, "{}", cursor::Goto(x as u16 + 1, y as u16 + 1));
+                    while x < cols && row[x] != cells[x] {
+                        let cell = cells[x];
+                        if let Some((_, depth)) = self.colors {
+                            if current != Some((cell.fg, cell.bg)) {
+                                out.push_str(&theme::escape(cell.fg, depth, false));
+                                out.push_str(&theme::escape(cell.bg, depth, true));
+                                current = Some((cell.fg, cell.bg));
+                            }
+                        }
+                        row[x] = cell;
+                        out.push(cell.glyph);
+                        x += 1;
+                    }
+                }
+            }
+            if current.is_some() {
+                let _ = write!(
+                    out,
+                    "{}{}",
+                    color::Fg(color::Reset),
+                    color::Bg(color::Reset)
+                );
+            }
+        }
+
+        if cleared || footer != self.footer {
+            let _ = write!(
+                out,
+                "{}{}{}",
+                cursor::Goto(1, height as u16 + 1),
+                clear::AfterCursor,
+                footer
+            );
+            self.footer.clear();
+            self.footer.push_str(footer);
+        }
+    }
+
+    // Terminal columns and rows taken up by the machine's screen.
+    fn size(&self, chip8: &Chip8) -> (usize, usize) {
+        let (cols, rows) = self.mode.cells(chip8.width(), chip8.height());
+        if self.double_width {
+            (cols * 2, rows)
+        } else {
+            (cols, rows)
+        }
+    }
+
+    // The character and colours for every cell of the machine's screen.
+    fn cells(&self, width: usize, height: usize) -> Vec<Vec<Cell>> {
+        let lit = |x: usize, y: usize| {
+            if x < width && y < height {
+                self.pixels[x + y * width]
+            } else {
+                (0, 0)
+            }
+        };
+        let pixel = |x: usize, y: usize| lit(x, y).0;
+        // Colour of a pixel, dimmed towards the background as it fades.
+        let shade = |theme: Theme, (planes, brightness): (u8, u8)| {
+            let (Rgb(r1, g1, b1), Rgb(r2, g2, b2)) =
+                (theme.colors[0], theme.colors[planes as usize]);
+            let mix = |from: u8, to: u8| {
+                let (from, to) = (i32::from(from), i32::from(to));
+                (from + (to - from) * i32::from(brightness) / 0xFF) as u8
+            };
+            Rgb(mix(r1, r2), mix(g1, g2), mix(b1, b2))
+        };
+        let (cell_width, cell_height) = self.mode.cell_size();
+        let (cols, rows) = self.mode.cells(width, height);
+
+        let mut cells = Vec::with_capacity(rows);
+        for row in 0..rows {
+            let (y, mut line) = (row * cell_height, Vec::with_capacity(cols * 2));
+            for col in 0..cols {
+                let x = col * cell_width;
+                let cell = match (self.mode, self.colors) {
+                    (RenderMode::Block, None) => Cell {
+                        glyph: PIXELS[pixel(x, y) as usize],
+                        ..BLANK
+                    },
+                    (RenderMode::Block, Some((theme, _))) => Cell {
+                        glyph: '█',
+                        fg: shade(theme, lit(x, y)),
+                        bg: theme.colors[0],
+                    },
+                    (RenderMode::HalfBlock, None) => {
+                        let top = (pixel(x, y) != 0) as usize;
+                        let bottom = (pixel(x, y + 1) != 0) as usize;
+                        Cell {
+                            glyph: HALF_BLOCKS[top | bottom << 1],
+                            ..BLANK
+                        }
+                    }
+                    (RenderMode::HalfBlock, Some((theme, _))) => Cell {
+                        glyph: '▀',
+                        fg: shade(theme, lit(x, y)),
+                        bg: shade(theme, lit(x, y + 1)),
+                    },
+                    (RenderMode::Braille, colors) => {
+                        // Dots are lit by either plane, and coloured by all
+                        // the planes lit anywhere in the cell.
+                        let (mut bits, mut planes, mut brightness) = (0, 0, 0);
+                        for (dx, dots) in BRAILLE_DOTS.iter().enumerate() {
+                            for (dy, &dot) in dots.iter().enumerate() {
+                                let (pix, bright) = lit(x + dx, y + dy);
+                                if pix != 0 {
+                                    bits |= dot;
+                                    planes |= pix;
+                                    brightness = brightness.max(bright);
+                                }
+                            }
+                        }
+                        let glyph = if bits == 0 {
+                            ' '
+                        } else {
+                            std::char::from_u32(BRAILLE_BLANK + bits).unwrap()
+                        };
+                        match colors {
+                            Some((theme, _)) => Cell {
+                                glyph,
+                                fg: shade(theme, (planes, brightness)),
+                                bg: theme.colors[0],
+                            },
+                            None => Cell { glyph, ..BLANK },
+                        }
+                    }
+                };
+                line.push(cell);
+                if self.double_width {
+                    line.push(cell);
+                }
+            }
+            cells.push(line);
+        }
+        cells
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glyphs(cells: &[Vec<Cell>]) -> Vec<String> {
+        cells
+            .iter()
+            .map(|row| row.iter().map(|cell| cell.glyph).collect())
+            .collect()
+    }
+
+    #[test]
+    fn redraws_only_changes() {
+        let mut chip8 = Chip8::new();
+        // DRW V0, V0, 1 twice: the top row of the 0 glyph, then erased.
+        chip8.load_rom(&[0xD0, 0x01, 0xD0, 0x01]).unwrap();
+        let mut screen = Screen::new(RenderMode::Block, false, None, 0);
+        let mut out = String::new();
+        screen.draw(&mut chip8, "footer", &mut out);
+        assert!(out.starts_with(&clear::All.to_string()));
+
+        out.clear();
+        screen.draw(&mut chip8, "footer", &mut out);
+        assert_eq!(out, "");
+
+        chip8.step().unwrap();
+        screen.draw(&mut chip8, "footer", &mut out);
+        assert_eq!(out, format!("{}████", cursor::Goto(1, 1)));
+
+        out.clear();
+        chip8.step().unwrap();
+        screen.draw(&mut chip8, "status", &mut out);
+        assert_eq!(
+            out,
+            format!(
+                "{}    {}{}status",
+                cursor::Goto(1, 1),
+                cursor::Goto(1, 33),
+                clear::AfterCursor
+            )
+        );
+    }
+
+    #[test]
+    fn packs_pixels_into_cells() {
+        let pixels = |screen: &mut Screen, lit: &[u8]| {
+            screen.pixels = lit.iter().map(|&pix| (pix, 0xFF * pix.min(1))).collect();
+        };
+
+        // A 2x4 screen with the left column and the bottom right pixel lit.
+        let lit = [1, 0, 1, 0, 1, 0, 1, 1];
+        let mut screen = Screen::new(RenderMode::HalfBlock, false, None, 0);
+        pixels(&mut screen, &lit);
+        assert_eq!(glyphs(&screen.cells(2, 4)), ["█ ", "█▄"]);
+
+        let mut screen = Screen::new(RenderMode::Braille, true, None, 0);
+        pixels(&mut screen, &lit);
+        assert_eq!(glyphs(&screen.cells(2, 4)), ["⣇⣇"]);
+
+        let mut screen = Screen::new(RenderMode::Block, false, None, 0);
+        pixels(&mut screen, &[0, 1, 2, 3]);
+        assert_eq!(glyphs(&screen.cells(4, 1)), [" █░▓"]);
+    }
+}
@@ -0,0 +1,363 @@
+use crate::debug::{self, Console};
+use crate::recording::Recording;
+use crate::screen::Screen;
+use rustichip8::audio::{AudioSink, Tone};
+use rustichip8::movie::Movie;
+use rustichip8::{Chip8, Keypad, Rewind, Scheduler, Speed};
+use std::error::Error;
+use std::fs;
+use std::io::{stdout, Stdout, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use termion::color;
+use termion::event::Key;
+use termion::input::TermRead;
+use termion::raw::{IntoRawMode, RawTerminal};
+
+// Terminals only report key presses, so a key counts as held until no
+// press (or auto-repeat) has been seen for this long.
+const KEY_HOLD: Duration = Duration::from_millis(200);
+// Hex keypad value for each index, laid out on 1234/QWER/ASDF/ZXCV.
+const KEY_MAP: [char; Keypad::NUM_KEYS] = [
+    'x', '1', '2', '3', 'q', 'w', 'e', 'a', 's', 'd', 'z', 'c', '4', 'r', 'f', 'v',
+];
+
+// Reserved keys, handled before the keypad mapping. Ctrl-C quits even while
+// the debugger prompt has the keyboard, the others are typed into it.
+const QUIT_KEY: Key = Key::Esc;
+const INTERRUPT_KEY: Key = Key::Ctrl('c');
+const PAUSE_KEY: Key = Key::Char('p');
+const RESET_KEY: Key = Key::F(2);
+const REWIND_KEY: Key = Key::Backspace;
+const SAVE_STATE_KEY: Key = Key::F(5);
+const LOAD_STATE_KEY: Key = Key::F(9);
+const RECORD_KEY: Key = Key::F(7);
+const HELP: &str =
+    "Esc quit, P pause, F2 reset, +/- speed, Backspace rewind, F5 save, F9 load, F7 record";
+
+/// Where keypad input comes from.
+pub enum Input {
+    Keyboard,
+    /// The keyboard, recording into the movie.
+    Record(Movie),
+    /// The movie, until it ends and the keyboard takes over.
+    Play(Movie),
+}
+
+/// Runs the machine in the terminal until it faults, the terminal can no longer
+/// be written to or the user quits with Esc, Ctrl-C, SIGINT or SIGTERM. However
+/// it ends, raw mode is left and the cursor shown again, even when unwinding
+/// from a panic. P pauses, F2 resets the machine with `rom`, + and - double or
+/// halve the speed, Backspace pauses and goes back a frame, F5 saves the
+/// machine to `state_path`, F9 restores it and F7 switches `recording` on and
+/// off. With `debug` set, the debugger console is shown under the screen and
+/// execution starts paused. `screen` draws the display and `audio` plays the
+/// tone while the sound timer runs. Hotkeys that would break a movie are
+/// ignored while `input` is recording or playing one.
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    chip8: &mut Chip8,
+    rom: &[u8],
+    speed: Speed,
+    state_path: &Path,
+    debug: bool,
+    mut screen: Screen,
+    audio: Option<&mut dyn AudioSink>,
+    input: &mut Input,
+    recording: &mut Recording,
+) -> Result<(), Box<dyn Error>> {
+    let interrupted = Arc::new(AtomicBool::new(false));
+    for &signal in &[signal_hook::consts::SIGINT, signal_hook::consts::SIGTERM] {
+        signal_hook::flag::register(signal, Arc::clone(&interrupted))
+            .expect("could not install signal handler");
+    }
+    let raw = stdout()
+        .into_raw_mode()
+        .map_err(|err| format!("Could not set up the terminal: {}", err))?;
+    let mut terminal = Restore(raw);
+    let mut console = if debug { Some(Console::new()) } else { None };
+    emulate(
+        chip8,
+        rom,
+        &mut Scheduler::new(speed),
+        state_path,
+        &mut console,
+        &mut screen,
+        audio,
+        input,
+        recording,
+        &mut terminal.0,
+        &interrupted,
+    )
+}
+
+// Puts the terminal back the way it was when dropped, whether `run` returns
+// or panics. Dropping the raw terminal inside leaves raw mode.
+struct Restore(RawTerminal<Stdout>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        let _ = write!(
+            self.0,
+            "{}{}{}\r\n",
+            color::Fg(color::Reset),
+            color::Bg(color::Reset),
+            termion::cursor::Show
+        );
+        let _ = self.0.flush();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn emulate(
+    chip8: &mut Chip8,
+    rom: &[u8],
+    scheduler: &mut Scheduler,
+    state_path: &Path,
+    console: &mut Option<Console>,
+    screen: &mut Screen,
+    mut audio: Option<&mut dyn AudioSink>,
+    input: &mut Input,
+    recording: &mut Recording,
+    stdout: &mut RawTerminal<Stdout>,
+    interrupted: &AtomicBool,
+) -> Result<(), Box<dyn Error>> {
+    let mut keys = termion::async_stdin().keys();
+    let mut key_seen: [Option<Instant>; Keypad::NUM_KEYS] = [None; Keypad::NUM_KEYS];
+    let mut footer = String::new();
+    let mut output = String::new();
+    let mut status = HELP.to_string();
+    let mut paused = false;
+    let mut rewind = Rewind::new(Rewind::DEFAULT_CAPACITY);
+    // Frames run so far, the position in a movie.
+    let mut frame = 0;
+    let mut next_frame = Instant::now();
+    loop {
+        let now = Instant::now();
+        for event in keys.by_ref() {
+            let key = match event {
+                Ok(key) => key,
+                Err(_) => continue,
+            };
+            if key == INTERRUPT_KEY {
+                return Ok(());
+            }
+            if let Some(console) = console.as_mut().filter(|console| console.is_paused()) {
+                console.handle_key(key, chip8, &mut rewind);
+                continue;
+            }
+            let movie = match input {
+                Input::Keyboard => false,
+                Input::Record(_) => true,
+                Input::Play(movie) => frame < movie.frames(),
+            };
+            match key {
+                QUIT_KEY => return Ok(()),
+                RESET_KEY
+                | REWIND_KEY
+                | LOAD_STATE_KEY
+                | Key::Char('+')
+                | Key::Char('=')
+                | Key::Char('-')
+                    if movie =>
+                {
+                    status = "Not while a movie is recording or playing".to_string();
+                }
+                PAUSE_KEY => match console {
+                    Some(console) => console.pause("Paused".to_string()),
+                    None => {
+                        paused = !paused;
+                        status = if paused {
+                            "Paused, P to resume".to_string()
+                        } else {
+                            HELP.to_string()
+                        };
+                    }
+                },
+                RESET_KEY => {
+                    status = match chip8.reset(rom) {
+                        Ok(()) => "Reset".to_string(),
+                        Err(err) => format!("Could not reset: {}", err),
+                    };
+                    key_seen = [None; Keypad::NUM_KEYS];
+                    rewind.clear();
+                }
+                REWIND_KEY => {
+                    if rewind.back_frame(chip8) {
+                        status = format!("Rewound, {} frames left", rewind.len());
+                        hold_keys(chip8, &mut key_seen, now);
+                    } else {
+                        status = "Nothing to rewind".to_string();
+                    }
+                    match console {
+                        Some(console) => console.pause(status.clone()),
+                        None => paused = true,
+                    }
+                }
+                Key::Char('+') | Key::Char('=') | Key::Char('-') => {
+                    let speed = if key == Key::Char('-') {
+                        scheduler.speed().halved()
+                    } else {
+                        scheduler.speed().doubled()
+                    };
+                    scheduler.set_speed(speed);
+                    status = format!("Speed: {}", speed);
+                }
+                RECORD_KEY => {
+                    status = match recording.toggle() {
+                        Ok(()) if recording.is_on() => {
+                            format!("Recording to {}", recording.path().display())
+                        }
+                        Ok(()) => "Recording paused".to_string(),
+                        Err(err) => format!("Could not record: {}", err),
+                    }
+                }
+                SAVE_STATE_KEY => status = save_state(chip8, state_path),
+                LOAD_STATE_KEY => {
+                    status = load_state(chip8, state_path);
+                    hold_keys(chip8, &mut key_seen, now);
+                    rewind.clear();
+                }
+                key => match console {
+                    Some(console) if key == debug::BREAK_KEY => console.pause("Paused".to_string()),
+                    _ => {
+                        if let Some(key) = map_char(key) {
+                            set_key(chip8, input, frame, key, true);
+                            key_seen[key as usize] = Some(now);
+                        }
+                    }
+                },
+            }
+        }
+        if interrupted.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        for (key, seen) in key_seen.iter_mut().enumerate() {
+            if let Some(t) = *seen {
+                if now.duration_since(t) >= KEY_HOLD {
+                    set_key(chip8, input, frame, key as u8, false);
+                    *seen = None;
+                }
+            }
+        }
+
+        match console {
+            Some(console) => {
+                console.run_frame(chip8, &mut rewind, scheduler.instructions_for_frame())
+            }
+            None if !paused => {
+                if let Input::Play(movie) = input {
+                    movie.play_frame(chip8, frame);
+                }
+                rewind.snapshot(chip8);
+                chip8.run_frame(scheduler.instructions_for_frame())?;
+                frame += 1;
+                match input {
+                    Input::Record(movie) => movie.end_frame(),
+                    Input::Play(movie) if frame == movie.frames() => {
+                        status = "Movie finished, the keyboard has taken over".to_string()
+                    }
+                    _ => {}
+                }
+            }
+            None => {}
+        }
+        let silent = paused || console.as_ref().is_some_and(|console| console.is_paused());
+        if let Some(sink) = audio.as_mut() {
+            // A failing sink is dropped rather than stopping the game.
+            let tone = if silent { Tone::Off } else { Tone::of(chip8) };
+            if let Err(err) = sink.frame(tone) {
+                status = format!("Audio output failed: {}", err);
+                audio = None;
+            }
+        }
+        // Paused frames are left out, so a pause doesn't stall the recording.
+        if !silent {
+            if let Err(err) = recording.frame(chip8) {
+                status = format!("Recording failed: {}", err);
+            }
+        }
+
+        footer.clear();
+        if let Some(console) = console {
+            console.render(chip8, &mut footer);
+            footer.push_str("\r\n");
+        }
+        footer.push_str(&status);
+        output.clear();
+        screen.draw(chip8, &footer, &mut output);
+        // Unlike the sinks, there's nowhere left to show a failing terminal's
+        // error but the caller.
+        if !output.is_empty() {
+            stdout
+                .write_all(output.as_bytes())
+                .and_then(|_| stdout.flush())
+                .map_err(|err| format!("Could not write to the terminal: {}", err))?;
+        }
+
+        match console {
+            Some(console) if console.wants_quit() => return Ok(()),
+            None if chip8.halted() => return Ok(()),
+            _ => {}
+        }
+
+        // Keep a steady 60 Hz, but don't try to catch up after falling behind.
+        next_frame += Scheduler::frame_duration();
+        let now = Instant::now();
+        if next_frame > now {
+            thread::sleep(next_frame - now);
+        } else {
+            next_frame = now;
+        }
+    }
+}
+
+// Presses or releases a key from the keyboard, which is ignored while a
+// movie is playing.
+fn set_key(chip8: &mut Chip8, input: &mut Input, frame: u64, key: u8, pressed: bool) {
+    match input {
+        Input::Keyboard => chip8.set_key(key, pressed),
+        Input::Record(movie) => movie.set_key(chip8, key, pressed),
+        Input::Play(movie) if frame >= movie.frames() => chip8.set_key(key, pressed),
+        Input::Play(_) => {}
+    }
+}
+
+// Lets keys held in a restored machine time out as usual.
+fn hold_keys(chip8: &Chip8, key_seen: &mut [Option<Instant>; Keypad::NUM_KEYS], now: Instant) {
+    for (key, seen) in key_seen.iter_mut().enumerate() {
+        *seen = if chip8.is_key_down(key as u8) {
+            Some(now)
+        } else {
+            None
+        };
+    }
+}
+
+fn save_state(chip8: &Chip8, path: &Path) -> String {
+    match fs::write(path, chip8.save_state()) {
+        Ok(()) => format!("Saved state to {}", path.display()),
+        Err(err) => format!("Could not save state to {}: {}", path.display(), err),
+    }
+}
+
+fn load_state(chip8: &mut Chip8, path: &Path) -> String {
+    let result = fs::read(path)
+        .map_err(|err| err.to_string())
+        .and_then(|state| chip8.load_state(&state).map_err(|err| err.to_string()));
+    match result {
+        Ok(()) => format!("Loaded state from {}", path.display()),
+        Err(err) => format!("Could not load state from {}: {}", path.display(), err),
+    }
+}
+
+fn map_char(key: Key) -> Option<u8> {
+    let c = match key {
+        Key::Char(c) => c.to_ascii_lowercase(),
+        _ => return None,
+    };
+    KEY_MAP.iter().position(|&k| k == c).map(|key| key as u8)
+}
@@ -0,0 +1,203 @@
+use rustichip8::capture::Palette;
+use std::env;
+use termion::color::{self, AnsiValue, Bg, Color, Fg, Rgb};
+
+/// How many colours the terminal can show.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorDepth {
+    /// The 8 standard ANSI colours.
+    Basic,
+    /// The xterm 256 colour palette.
+    Ansi256,
+    /// 24-bit RGB.
+    TrueColor,
+}
+
+impl ColorDepth {
+    pub fn from_name(name: &str) -> Option<ColorDepth> {
+        match name.to_ascii_lowercase().as_str() {
+            "8" => Some(ColorDepth::Basic),
+            "256" => Some(ColorDepth::Ansi256),
+            "truecolor" | "24bit" => Some(ColorDepth::TrueColor),
+            _ => None,
+        }
+    }
+
+    /// Truecolor if `COLORTERM` says the terminal supports it, otherwise 256
+    /// colours, which nearly every terminal emulator has.
+    pub fn detect() -> ColorDepth {
+        match env::var("COLORTERM") {
+            Ok(ref term) if term == "truecolor" || term == "24bit" => ColorDepth::TrueColor,
+            _ => ColorDepth::Ansi256,
+        }
+    }
+}
+
+// The standard colours with their usual RGB values, for picking the nearest.
+const BASIC_COLORS: [(Rgb, &dyn Color); 8] = [
+    (Rgb(0x00, 0x00, 0x00), &color::Black),
+    (Rgb(0xCD, 0x00, 0x00), &color::Red),
+    (Rgb(0x00, 0xCD, 0x00), &color::Green),
+    (Rgb(0xCD, 0xCD, 0x00), &color::Yellow),
+    (Rgb(0x00, 0x00, 0xEE), &color::Blue),
+    (Rgb(0xCD, 0x00, 0xCD), &color::Magenta),
+    (Rgb(0x00, 0xCD, 0xCD), &color::Cyan),
+    (Rgb(0xE5, 0xE5, 0xE5), &color::White),
+];
+
+/// Colours for the four combinations of lit XO-CHIP bitplanes: none, the
+/// first plane only, the second plane only, and both. Plain CHIP-8 programs
+/// only use the first two.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    pub colors: [Rgb; 4],
+}
+
+impl Theme {
+    /// Octo's default palette.
+    pub const OCTO: Theme = Theme {
+        colors: [
+            Rgb(0x99, 0x66, 0x00),
+            Rgb(0xFF, 0xCC, 0x00),
+            Rgb(0xFF, 0x66, 0x00),
+            Rgb(0x66, 0x22, 0x00),
+        ],
+    };
+
+    /// Amber monochrome monitor.
+    pub const AMBER: Theme = Theme {
+        colors: [
+            Rgb(0x1A, 0x10, 0x00),
+            Rgb(0xFF, 0xB0, 0x00),
+            Rgb(0x99, 0x66, 0x00),
+            Rgb(0xFF, 0xDD, 0x88),
+        ],
+    };
+
+    /// Green phosphor monitor.
+    pub const GREEN: Theme = Theme {
+        colors: [
+            Rgb(0x00, 0x14, 0x00),
+            Rgb(0x33, 0xFF, 0x33),
+            Rgb(0x1A, 0x99, 0x1A),
+            Rgb(0xAA, 0xFF, 0xAA),
+        ],
+    };
+
+    /// Names accepted by `from_name`, paired with their themes.
+    pub const PRESETS: [(&'static str, Theme); 3] = [
+        ("octo", Theme::OCTO),
+        ("amber", Theme::AMBER),
+        ("green", Theme::GREEN),
+    ];
+
+    /// Looks up a preset by name, or failing that parses a custom palette:
+    /// comma separated `#RRGGBB` colours for the background and foreground,
+    /// optionally followed by the second plane and both planes.
+    pub fn from_name(name: &str) -> Result<Theme, String> {
+        if let Some((_, theme)) = Theme::PRESETS
+            .iter()
+            .find(|(preset, _)| preset.eq_ignore_ascii_case(name))
+        {
+            return Ok(*theme);
+        }
+        let colors = name
+            .split(',')
+            .map(|color| parse_rgb(color.trim()))
+            .collect::<Option<Vec<Rgb>>>()
+            .ok_or_else(|| format!("Unknown theme {}", name))?;
+        match colors[..] {
+            [background, foreground] => Ok(Theme {
+                colors: [background, foreground, foreground, foreground],
+            }),
+            [background, plane1, plane2, both] => Ok(Theme {
+                colors: [background, plane1, plane2, both],
+            }),
+            _ => Err(format!(
+                "A theme needs 2 or 4 colours, got {}",
+                colors.len()
+            )),
+        }
+    }
+
+    /// The colours as RGB triples, for image output.
+    pub fn palette(&self) -> Palette {
+        self.colors.map(|Rgb(r, g, b)| [r, g, b])
+    }
+}
+
+fn parse_rgb(text: &str) -> Option<Rgb> {
+    let hex = text.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    Some(Rgb((value >> 16) as u8, (value >> 8) as u8, value as u8))
+}
+
+/// The escape sequence that sets the foreground, or with `background` the
+/// background, to the closest colour the terminal can show.
+pub fn escape(rgb: Rgb, depth: ColorDepth, background: bool) -> String {
+    fn write<C: Color>(color: C, background: bool) -> String {
+        if background {
+            Bg(color).to_string()
+        } else {
+            Fg(color).to_string()
+        }
+    }
+
+    let Rgb(r, g, b) = rgb;
+    match depth {
+        ColorDepth::TrueColor => write(rgb, background),
+        ColorDepth::Ansi256 => {
+            let level = |c: u8| ((u16::from(c) * 5 + 127) / 255) as u8;
+            write(AnsiValue::rgb(level(r), level(g), level(b)), background)
+        }
+        ColorDepth::Basic => {
+            let distance = |&(Rgb(r2, g2, b2), _): &(Rgb, &dyn Color)| {
+                let d = |a: u8, b: u8| (i32::from(a) - i32::from(b)).pow(2);
+                d(r, r2) + d(g, g2) + d(b, b2)
+            };
+            let (_, color) = BASIC_COLORS.iter().min_by_key(|c| distance(c)).unwrap();
+            write(*color, background)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_presets_and_custom_palettes() {
+        assert_eq!(Theme::from_name("Amber"), Ok(Theme::AMBER));
+        let two = Theme::from_name("#000000, #FFaa00").unwrap();
+        assert_eq!(two.colors[0], Rgb(0, 0, 0));
+        assert_eq!(two.colors[1..], [Rgb(0xFF, 0xAA, 0x00); 3]);
+        let four = Theme::from_name("#000000,#111111,#222222,#333333").unwrap();
+        assert_eq!(four.colors[2], Rgb(0x22, 0x22, 0x22));
+
+        assert!(Theme::from_name("#000000,#111111,#222222").is_err());
+        assert!(Theme::from_name("#00000,#111111").is_err());
+        assert!(Theme::from_name("purple").is_err());
+    }
+
+    #[test]
+    fn escapes_for_each_depth() {
+        let orange = Rgb(0xFF, 0x80, 0x00);
+        assert_eq!(
+            escape(orange, ColorDepth::TrueColor, false),
+            "\x1b[38;2;255;128;0m"
+        );
+        // 5, 3, 0 in the 6x6x6 cube.
+        assert_eq!(escape(orange, ColorDepth::Ansi256, true), "\x1b[48;5;214m");
+        assert_eq!(
+            escape(Rgb(0xC0, 0x10, 0x10), ColorDepth::Basic, false),
+            Fg(color::Red).to_string()
+        );
+        assert_eq!(
+            escape(Rgb(0x10, 0x10, 0x10), ColorDepth::Basic, true),
+            Bg(color::Black).to_string()
+        );
+    }
+}
@@ -0,0 +1,179 @@
+//! Runs test ROMs assembled from the sources in `tests/roms` headlessly and
+//! compares the final screen with the golden ASCII dumps in `tests/golden`.
+//! Run with `UPDATE_GOLDEN=1` to write the golden files from the
+//! current output, then check the new dumps by eye before committing them.
+
+use rustichip8::dump::{self, DumpFormat};
+use rustichip8::movie::Movie;
+use rustichip8::{asm, headless, Chip8, Quirks, Scheduler, Speed};
+use std::env;
+use std::fs;
+use std::path::Path;
+
+const SPEED: Speed = Speed::InstructionsPerFrame(1000);
+const CYCLES: u64 = 100_000;
+
+fn load(name: &str) -> Vec<u8> {
+    let source = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/roms")
+        .join(format!("{}.asm", name));
+    let text = fs::read_to_string(&source).unwrap();
+    asm::assemble(&text).unwrap_or_else(|err| panic!("{}.asm:{}", name, err))
+}
+
+fn machine(name: &str, xo_chip: bool, quirks: Option<Quirks>) -> Chip8 {
+    let rom = load(name);
+    let mut chip8 = if xo_chip {
+        Chip8::new_xo_chip()
+    } else {
+        Chip8::new()
+    };
+    if let Some(quirks) = quirks {
+        chip8.set_quirks(quirks);
+    }
+    chip8.load_rom(&rom).unwrap();
+    chip8
+}
+
+fn run(chip8: &mut Chip8) {
+    headless::run(chip8, SPEED, CYCLES).unwrap();
+}
+
+fn check_screen(chip8: &Chip8, golden: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}.txt", golden));
+    let actual = String::from_utf8(dump::dump(chip8, DumpFormat::Ascii)).unwrap();
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, &actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path).unwrap_or_else(|_| {
+        panic!(
+            "no golden screen {}, run with UPDATE_GOLDEN=1 to create it\n{}",
+            path.display(),
+            actual
+        )
+    });
+    assert!(
+        actual == expected,
+        "screen for {} differs\nexpected:\n{}\nactual:\n{}",
+        golden,
+        expected,
+        actual
+    );
+}
+
+fn check_rom(name: &str, xo_chip: bool, quirks: Option<Quirks>, golden: &str) {
+    let mut chip8 = machine(name, xo_chip, quirks);
+    run(&mut chip8);
+    check_screen(&chip8, golden);
+}
+
+#[test]
+fn alu_flags() {
+    check_rom("alu_flags", false, None, "alu_flags");
+}
+
+#[test]
+fn font_bcd() {
+    check_rom("font_bcd", false, None, "font_bcd");
+}
+
+#[test]
+fn quirks_vip() {
+    check_rom(
+        "quirks_probe",
+        false,
+        Some(Quirks::COSMAC_VIP),
+        "quirks_vip",
+    );
+}
+
+#[test]
+fn quirks_chip48() {
+    check_rom(
+        "quirks_probe",
+        false,
+        Some(Quirks::CHIP_48),
+        "quirks_chip48",
+    );
+}
+
+#[test]
+fn quirks_schip() {
+    check_rom("quirks_probe", false, Some(Quirks::SCHIP), "quirks_schip");
+}
+
+#[test]
+fn quirks_xo_chip() {
+    check_rom("quirks_probe", true, None, "quirks_xo_chip");
+}
+
+#[test]
+fn keypad_input() {
+    let mut chip8 = machine("keypad_input", false, None);
+    run(&mut chip8);
+    assert!(chip8.is_waiting_for_key());
+    chip8.set_key(0xA, true);
+    chip8.set_key(0xA, false);
+    run(&mut chip8);
+    assert!(!chip8.is_spinning());
+    chip8.set_key(0x7, true);
+    run(&mut chip8);
+    assert!(chip8.is_spinning());
+    check_screen(&chip8, "keypad_input");
+}
+
+#[test]
+fn movie_playback() {
+    let rom = load("keypad_input");
+    let mut chip8 = machine("keypad_input", false, None);
+    let speed = Speed::InstructionsPerFrame(15);
+    let mut movie = Movie::new(&chip8, &rom, speed);
+    let mut scheduler = Scheduler::new(speed);
+    // Tap A, then hold 7.
+    let keys = [(10, 0xA, true), (20, 0xA, false), (40, 0x7, true)];
+    for frame in 0..60 {
+        for &(_, key, pressed) in keys.iter().filter(|&&(at, _, _)| at == frame) {
+            movie.set_key(&mut chip8, key, pressed);
+        }
+        chip8.run_frame(scheduler.instructions_for_frame()).unwrap();
+        movie.end_frame();
+    }
+    check_screen(&chip8, "keypad_input");
+
+    let movie = Movie::from_bytes(&movie.to_bytes()).unwrap();
+    let mut replay = movie.machine(&rom).unwrap();
+    headless::play(&mut replay, &movie).unwrap();
+    assert!(replay.save_state() == chip8.save_state());
+}
+
+#[test]
+fn schip_hires() {
+    let mut chip8 = machine("schip_hires", false, None);
+    run(&mut chip8);
+    assert!(chip8.halted());
+    check_screen(&chip8, "schip_hires");
+}
+
+#[test]
+fn xo_chip_planes() {
+    let mut chip8 = machine("xochip_planes", true, None);
+    run(&mut chip8);
+    assert!(chip8.halted());
+    check_screen(&chip8, "xochip_planes");
+}
+
+#[test]
+fn random_dots() {
+    let seeded = || {
+        let mut chip8 = machine("random_dots", false, None);
+        chip8.set_seed(0x5EED);
+        run(&mut chip8);
+        assert!(chip8.is_spinning());
+        chip8
+    };
+    assert_eq!(seeded().framebuffer(), seeded().framebuffer());
+    check_screen(&seeded(), "random_dots");
+}
@@ -0,0 +1,125 @@
+; Checks the results and VF of the arithmetic instructions. Every check draws
+; a 1 if it passed and a 0 if it failed, so the screen should be all ones.
+; VA and VB hold the position of the next digit.
+
+        LD VA, 0
+        LD VB, 0
+
+; 8XY4 without and with carry
+        LD V1, 1
+        LD V2, 2
+        ADD V1, V2
+        LD V3, 3
+        LD V4, 0
+        CALL check
+        LD V1, #FF
+        LD V2, 1
+        ADD V1, V2
+        LD V3, 0
+        LD V4, 1
+        CALL check
+
+; 8XY5 without and with borrow, including operands above 127
+        LD V1, 5
+        LD V2, 3
+        SUB V1, V2
+        LD V3, 2
+        LD V4, 1
+        CALL check
+        LD V1, 3
+        LD V2, 5
+        SUB V1, V2
+        LD V3, #FE
+        LD V4, 0
+        CALL check
+        LD V1, 200
+        LD V2, 100
+        SUB V1, V2
+        LD V3, 100
+        LD V4, 1
+        CALL check
+        LD V1, 100
+        LD V2, 200
+        SUB V1, V2
+        LD V3, 156
+        LD V4, 0
+        CALL check
+
+; 8XY7 without and with borrow
+        LD V1, 3
+        LD V2, 5
+        SUBN V1, V2
+        LD V3, 2
+        LD V4, 1
+        CALL check
+        LD V1, 5
+        LD V2, 3
+        SUBN V1, V2
+        LD V3, #FE
+        LD V4, 0
+        CALL check
+
+; 8XY6 and 8XYE put the shifted out bit in VF
+        LD V1, 5
+        SHR V1
+        LD V3, 2
+        LD V4, 1
+        CALL check
+        LD V1, #81
+        SHL V1
+        LD V3, 2
+        LD V4, 1
+        CALL check
+        LD V1, #7F
+        SHL V1
+        LD V3, #FE
+        LD V4, 0
+        CALL check
+
+; With VF as an operand the flag is written last
+        LD VF, #FF
+        LD V1, 1
+        ADD VF, V1
+        LD V1, VF
+        LD V3, 1
+        LD V4, 1
+        CALL check
+        LD V1, 5
+        LD VF, 3
+        SUB V1, VF
+        LD V3, 2
+        LD V4, 1
+        CALL check
+        LD VF, 3
+        SHR VF
+        LD V1, VF
+        LD V3, 1
+        LD V4, 1
+        CALL check
+
+; 7XNN wraps and leaves VF alone
+        LD VF, 0
+        LD V1, #FF
+        ADD V1, 2
+        LD V3, 1
+        LD V4, 0
+        CALL check
+
+end:    JP end
+
+; Compares V1 with V3 and VF with V4.
+check:  LD V0, 0
+        SE V1, V3
+        JP show
+        SE VF, V4
+        JP show
+        LD V0, 1
+; Draws the digit in V0 and moves to the next position.
+show:   LD F, V0
+        DRW VA, VB, 5
+        ADD VA, 5
+        SE VA, 60
+        RET
+        LD VA, 0
+        ADD VB, 6
+        RET
@@ -0,0 +1,34 @@
+; Draws the sixteen font digits, then the BCD digits of 234.
+
+        LD VA, 0
+        LD VB, 0
+        LD V0, 0
+digits: CALL show
+        ADD V0, 1
+        SE V0, 16
+        JP digits
+
+        LD VA, 0
+        LD VB, 12
+        LD V5, 234
+        LD I, bcd
+        LD B, V5
+        LD V2, [I]
+        CALL show
+        LD V0, V1
+        CALL show
+        LD V0, V2
+        CALL show
+
+end:    JP end
+
+show:   LD F, V0
+        DRW VA, VB, 5
+        ADD VA, 5
+        SE VA, 60
+        RET
+        LD VA, 0
+        ADD VB, 6
+        RET
+
+bcd:    DB 0, 0, 0
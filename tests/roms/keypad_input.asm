@@ -0,0 +1,21 @@
+; Waits for a key with FX0A and draws it, then waits for key 7 to be held
+; down with EXA1 and draws 7.
+
+        LD VA, 0
+        LD VB, 0
+        LD V0, K
+        CALL show
+
+        LD V1, 7
+wait:   SKNP V1
+        JP held
+        JP wait
+held:   LD V0, V1
+        CALL show
+
+end:    JP end
+
+show:   LD F, V0
+        DRW VA, VB, 5
+        ADD VA, 5
+        RET
@@ -0,0 +1,62 @@
+; Draws one digit for each quirk, showing which behaviour the interpreter
+; picked:
+;   VF after 8XY1     0 if reset, 1 if untouched
+;   8XY6 source       2 if VY was shifted, 0 if VX was
+;   FX55 increment    how far I moved after saving V0-V1
+;   BNNN offset       1 if VX was added, 0 if V0 was
+;   sprite edges      1 if sprites wrap, 0 if they are clipped
+
+        LD VA, 0
+        LD VB, 0
+
+        LD VF, 1
+        LD V1, 1
+        LD V2, 2
+        OR V1, V2
+        LD V0, VF
+        CALL show
+
+        LD V1, 0
+        LD V2, 4
+        SHR V1, V2
+        LD V0, V1
+        CALL show
+
+        LD I, buffer
+        LD V0, 0
+        LD V1, 1
+        LD [I], V1
+        LD V0, [I]
+        CALL show
+
+; The table sits at 2xx, so with the quirk BNNN adds V2 and picks t1.
+        LD V0, 0
+        LD V2, 2
+        JP V0, table
+jumped: CALL show
+
+        LD I, line
+        LD V5, 0
+        LD V6, 10
+        DRW V5, V6, 1
+        LD V5, 60
+        DRW V5, V6, 1
+        LD V0, VF
+        CALL show
+
+end:    JP end
+
+table:  JP t0
+        JP t1
+t0:     LD V0, 0
+        JP jumped
+t1:     LD V0, 1
+        JP jumped
+
+show:   LD F, V0
+        DRW VA, VB, 5
+        ADD VA, 5
+        RET
+
+buffer: DB 0, 1, 2, 3
+line:   DB #FF
@@ -0,0 +1,15 @@
+; Plots 32 dots with RND, so a seeded run must draw the same screen every
+; time.
+
+        LD I, dot
+        LD V2, 32
+plot:   RND V0, 63
+        RND V1, 31
+        DRW V0, V1, 1
+        ADD V2, 255
+        SE V2, 0
+        JP plot
+
+end:    JP end
+
+dot:    DB 0x80
@@ -0,0 +1,26 @@
+; Draws the big font digits 0-9 and a hollow 16x16 square in hi-res, then
+; scrolls everything right by 4 and down by 2 and exits.
+
+        HIGH
+        LD VA, 0
+        LD VB, 0
+        LD V0, 0
+big:    LD HF, V0
+        DRW VA, VB, 10
+        ADD VA, 10
+        ADD V0, 1
+        SE V0, 10
+        JP big
+
+        LD I, square
+        LD V1, 20
+        LD V2, 20
+        DRW V1, V2, 0
+        SCR
+        SCD 2
+        EXIT
+
+square: DB #FF, #FF
+        DB #80, #01, #80, #01, #80, #01, #80, #01, #80, #01, #80, #01, #80, #01
+        DB #80, #01, #80, #01, #80, #01, #80, #01, #80, #01, #80, #01, #80, #01
+        DB #FF, #FF
@@ -0,0 +1,36 @@
+; Draws overlapping blocks on both bitplanes, so all four plane combinations
+; appear, then round-trips V3-V4 through memory with 5XY2/5XY3 and draws
+; them on the first plane.
+
+        LD I, LONG blocks
+        PLANE 3
+        LD V1, 0
+        LD V2, 0
+        DRW V1, V2, 4
+        PLANE 2
+        LD V1, 10
+        LD I, LONG block
+        DRW V1, V2, 4
+
+        LD V3, 1
+        LD V4, 2
+        LD I, scratch
+        LD [I], V3-V4
+        LD V3, 0
+        LD V4, 0
+        LD V3-V4, [I]
+
+        PLANE 1
+        LD V1, 0
+        LD V2, 8
+        LD F, V3
+        DRW V1, V2, 5
+        LD V1, 5
+        LD F, V4
+        DRW V1, V2, 5
+        EXIT
+
+blocks: DB #F0, #F0, #F0, #F0
+        DB #3C, #3C, #3C, #3C
+block:  DB #F0, #90, #90, #F0
+scratch: DB 0, 0